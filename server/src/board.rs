@@ -0,0 +1,209 @@
+// ------------------------------------------------------------
+// Board
+// ------------------------------------------------------------
+
+/// How the tiles of a board are laid out and which tiles touch.
+#[derive(spacetimedb::SpacetimeType, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Topology {
+    /// Square tiles in a `width` x `height` rectangle, touching along edges (and corners for diagonal attacks)
+    Rectangular,
+    /// Hexagonal tiles in axial coordinates (`x` is q, `y` is r), filling a `width` x `height` rhombus
+    Hex,
+}
+
+// Neighbor offsets of a tile, as (dx, dy)
+const ORTHOGONAL_OFFSETS: [(i64, i64); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const DIAGONAL_OFFSETS: [(i64, i64); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const HEX_OFFSETS: [(i64, i64); 6] = [(1, 0), (-1, 0), (0, 1), (0, -1), (1, -1), (-1, 1)];
+
+/// The shape of a game's board, which answers every question about tile positions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Board {
+    pub topology: Topology,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Board {
+    /// Returns whether the coordinates are on the board.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x < self.width && y < self.height
+    }
+
+    /// Returns the coordinates of every tile on the board, in x, then y order.
+    pub fn tiles(&self) -> impl Iterator<Item = (u32, u32)> {
+        let height = self.height;
+        (0..self.width).flat_map(move |x| (0..height).map(move |y| (x, y)))
+    }
+
+    /// Returns the tiles touching a tile, which is what every movement, attack
+    /// and pathing rule builds on.
+    ///
+    /// Tiles touching only at a corner are included when `allow_diagonal` is set.
+    /// Hex tiles have no corners of their own, so hex boards ignore it.
+    pub fn neighbors(&self, x: u32, y: u32, allow_diagonal: bool) -> Vec<(u32, u32)> {
+        let offsets: Vec<(i64, i64)> = match self.topology {
+            Topology::Rectangular if allow_diagonal => ORTHOGONAL_OFFSETS
+                .iter()
+                .chain(DIAGONAL_OFFSETS.iter())
+                .copied()
+                .collect(),
+            Topology::Rectangular => ORTHOGONAL_OFFSETS.to_vec(),
+            Topology::Hex => HEX_OFFSETS.to_vec(),
+        };
+
+        offsets
+            .into_iter()
+            .filter_map(|(dx, dy)| {
+                let nx = u32::try_from(x as i64 + dx).ok()?;
+                let ny = u32::try_from(y as i64 + dy).ok()?;
+                self.contains(nx, ny).then_some((nx, ny))
+            })
+            .collect()
+    }
+
+    /// Checks whether two tiles are next to each other on the board.
+    pub fn are_adjacent(&self, a: (u32, u32), b: (u32, u32), allow_diagonal: bool) -> bool {
+        self.neighbors(a.0, a.1, allow_diagonal).contains(&b)
+    }
+
+    /// Returns how far apart two tiles are, squared.
+    ///
+    /// Rectangular boards measure the straight line between the tiles, while hex
+    /// boards count the steps between them.
+    pub fn distance_squared(&self, a: (u32, u32), b: (u32, u32)) -> u64 {
+        let dx = a.0 as i64 - b.0 as i64;
+        let dy = a.1 as i64 - b.1 as i64;
+        match self.topology {
+            Topology::Rectangular => (dx * dx + dy * dy) as u64,
+            Topology::Hex => {
+                let steps = (dx.abs() + dy.abs() + (dx + dy).abs()) / 2;
+                (steps * steps) as u64
+            }
+        }
+    }
+
+    /// Returns the base tile of each seat, in seat order, spread roughly evenly over the board.
+    ///
+    /// Bases are kept one tile in from the edge. The first four seats start in the
+    /// corners, in the same order as before more than 5 players were supported, and
+    /// every following seat takes the tile farthest from all bases placed before it
+    /// (the first such tile in x, then y order on a tie). On the default 7x7 board
+    /// with 5 players this gives (1,1), (5,1), (1,5), (5,5) and (3,3).
+    pub fn start_positions(&self, player_count: u32) -> Vec<(u32, u32)> {
+        let (far_x, far_y) = (self.width - 2, self.height - 2);
+        let mut starts: Vec<(u32, u32)> = [(1, 1), (far_x, 1), (1, far_y), (far_x, far_y)]
+            .into_iter()
+            .take(player_count as usize)
+            .collect();
+        while (starts.len() as u32) < player_count {
+            let mut best = None;
+            let mut best_distance = 0;
+            for x in 1..self.width - 1 {
+                for y in 1..self.height - 1 {
+                    // Distance to the nearest base placed so far
+                    let distance = starts
+                        .iter()
+                        .map(|&start| self.distance_squared((x, y), start))
+                        .min()
+                        .unwrap_or(0);
+                    if distance > best_distance {
+                        best = Some((x, y));
+                        best_distance = distance;
+                    }
+                }
+            }
+
+            // Every inner tile is taken, which the board size limits rule out
+            let Some(start) = best else {
+                break;
+            };
+            starts.push(start);
+        }
+        starts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(topology: Topology, width: u32, height: u32) -> Board {
+        Board {
+            topology,
+            width,
+            height,
+        }
+    }
+
+    fn sorted(mut tiles: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+        tiles.sort_unstable();
+        tiles
+    }
+
+    #[test]
+    fn square_tiles_touch_along_their_edges() {
+        let board = board(Topology::Rectangular, 7, 7);
+        assert_eq!(
+            sorted(board.neighbors(3, 3, false)),
+            [(2, 3), (3, 2), (3, 4), (4, 3)]
+        );
+        assert_eq!(sorted(board.neighbors(0, 0, false)), [(0, 1), (1, 0)]);
+        assert_eq!(sorted(board.neighbors(6, 6, false)), [(5, 6), (6, 5)]);
+    }
+
+    #[test]
+    fn diagonals_add_the_corner_tiles() {
+        let board = board(Topology::Rectangular, 7, 7);
+        assert_eq!(board.neighbors(3, 3, true).len(), 8);
+        assert_eq!(
+            sorted(board.neighbors(0, 0, true)),
+            [(0, 1), (1, 0), (1, 1)]
+        );
+        assert!(!board.are_adjacent((3, 3), (4, 4), false));
+        assert!(board.are_adjacent((3, 3), (4, 4), true));
+    }
+
+    #[test]
+    fn hex_tiles_have_six_neighbors_and_no_diagonals() {
+        let board = board(Topology::Hex, 7, 7);
+        let expected = [(2, 3), (2, 4), (3, 2), (3, 4), (4, 2), (4, 3)];
+        assert_eq!(sorted(board.neighbors(3, 3, false)), expected);
+        assert_eq!(sorted(board.neighbors(3, 3, true)), expected);
+        assert!(!board.are_adjacent((3, 3), (4, 4), true));
+    }
+
+    #[test]
+    fn hex_distance_counts_steps() {
+        let board = board(Topology::Hex, 7, 7);
+        assert_eq!(board.distance_squared((0, 0), (2, 0)), 4);
+        assert_eq!(board.distance_squared((2, 0), (0, 2)), 4);
+        assert_eq!(board.distance_squared((0, 0), (2, 2)), 16);
+    }
+
+    #[test]
+    fn default_bases_are_spread_over_the_board() {
+        let board = board(Topology::Rectangular, 7, 7);
+        assert_eq!(
+            board.start_positions(5),
+            [(1, 1), (5, 1), (1, 5), (5, 5), (3, 3)]
+        );
+        assert_eq!(board.start_positions(2), [(1, 1), (5, 1)]);
+    }
+
+    #[test]
+    fn bases_stay_one_tile_in_from_the_edge() {
+        for topology in [Topology::Rectangular, Topology::Hex] {
+            let board = board(topology, 10, 6);
+            let starts = board.start_positions(8);
+            assert_eq!(starts.len(), 8);
+            for &(x, y) in &starts {
+                assert!((1..9).contains(&x) && (1..5).contains(&y));
+            }
+            let mut unique = starts.clone();
+            unique.sort_unstable();
+            unique.dedup();
+            assert_eq!(unique.len(), 8);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+// ------------------------------------------------------------
+// Card Hands
+// ------------------------------------------------------------
+
+/// A poker hand formed by every card a player plays at once, weakest first.
+#[derive(spacetimedb::SpacetimeType, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HandRank {
+    Pair,
+    ThreeOfAKind,
+    Straight,
+    Flush,
+    FullHouse,
+    FourOfAKind,
+    StraightFlush,
+}
+
+/// What a player receives for playing a hand.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HandReward {
+    pub tanks: u32,        // Tanks added to the target tile
+    pub gold: u32,         // Gold added to the player
+    pub defense_buff: u32, // Extra defense given to the target tile
+    pub buff_turns: u64,   // Number of turns the defense buff lasts
+}
+
+impl HandRank {
+    /// Returns the reward for playing this hand, which grows with the hand's strength.
+    pub fn reward(self) -> HandReward {
+        let (tanks, gold, defense_buff, buff_turns) = match self {
+            HandRank::Pair => (1, 0, 0, 0),
+            HandRank::ThreeOfAKind => (2, 0, 0, 0),
+            HandRank::Straight => (2, 3, 0, 0),
+            HandRank::Flush => (2, 0, 2, 2),
+            HandRank::FullHouse => (3, 3, 0, 0),
+            HandRank::FourOfAKind => (4, 0, 2, 2),
+            HandRank::StraightFlush => (5, 5, 3, 3),
+        };
+        HandReward {
+            tanks,
+            gold,
+            defense_buff,
+            buff_turns,
+        }
+    }
+}
+
+/// Works out which hand a set of cards forms, if any.
+///
+/// Every card must count towards the hand, so hands are exactly 2 cards (pair),
+/// 3 cards (three of a kind), 4 cards (four of a kind) or 5 cards (straight,
+/// flush, full house or straight flush). Aces count as 1 or as 14 in straights.
+///
+/// # Arguments
+/// * `cards` - The `(suit, value)` of each card, with values from 1 (Ace) to 13 (King)
+///
+/// # Returns
+/// * `Option<HandRank>` - The hand formed, or None if the cards don't form one
+pub fn evaluate_hand(cards: &[(&str, u8)]) -> Option<HandRank> {
+    // Count how many cards share each value, most common first
+    let mut values: Vec<u8> = cards.iter().map(|&(_, value)| value).collect();
+    values.sort_unstable();
+    let mut groups: Vec<usize> = Vec::new();
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 && values[i - 1] == *value {
+            *groups.last_mut()? += 1;
+        } else {
+            groups.push(1);
+        }
+    }
+    groups.sort_unstable_by(|a, b| b.cmp(a));
+
+    match (cards.len(), groups.as_slice()) {
+        (2, [2]) => Some(HandRank::Pair),
+        (3, [3]) => Some(HandRank::ThreeOfAKind),
+        (4, [4]) => Some(HandRank::FourOfAKind),
+        (5, [3, 2]) => Some(HandRank::FullHouse),
+        (5, _) => {
+            let flush = cards.iter().all(|&(suit, _)| suit == cards[0].0);
+            let straight =
+                groups.len() == 5 && (values[4] - values[0] == 4 || values == [1, 10, 11, 12, 13]);
+            match (straight, flush) {
+                (true, true) => Some(HandRank::StraightFlush),
+                (false, true) => Some(HandRank::Flush),
+                (true, false) => Some(HandRank::Straight),
+                (false, false) => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hand(cards: &[(&'static str, u8)]) -> Option<HandRank> {
+        evaluate_hand(cards)
+    }
+
+    #[test]
+    fn every_hand_is_recognised() {
+        assert_eq!(hand(&[("hearts", 7), ("spades", 7)]), Some(HandRank::Pair));
+        assert_eq!(
+            hand(&[("hearts", 7), ("spades", 7), ("clubs", 7)]),
+            Some(HandRank::ThreeOfAKind)
+        );
+        assert_eq!(
+            hand(&[("hearts", 7), ("spades", 7), ("clubs", 7), ("diamonds", 7)]),
+            Some(HandRank::FourOfAKind)
+        );
+        assert_eq!(
+            hand(&[
+                ("hearts", 4),
+                ("spades", 5),
+                ("clubs", 6),
+                ("hearts", 7),
+                ("hearts", 8)
+            ]),
+            Some(HandRank::Straight)
+        );
+        assert_eq!(
+            hand(&[
+                ("hearts", 2),
+                ("hearts", 5),
+                ("hearts", 9),
+                ("hearts", 11),
+                ("hearts", 13)
+            ]),
+            Some(HandRank::Flush)
+        );
+        assert_eq!(
+            hand(&[
+                ("hearts", 7),
+                ("spades", 7),
+                ("clubs", 7),
+                ("hearts", 2),
+                ("spades", 2)
+            ]),
+            Some(HandRank::FullHouse)
+        );
+        assert_eq!(
+            hand(&[
+                ("clubs", 9),
+                ("clubs", 10),
+                ("clubs", 11),
+                ("clubs", 12),
+                ("clubs", 13)
+            ]),
+            Some(HandRank::StraightFlush)
+        );
+    }
+
+    #[test]
+    fn aces_are_low_or_high_in_straights() {
+        assert_eq!(
+            hand(&[
+                ("hearts", 1),
+                ("spades", 2),
+                ("clubs", 3),
+                ("hearts", 4),
+                ("hearts", 5)
+            ]),
+            Some(HandRank::Straight)
+        );
+        assert_eq!(
+            hand(&[
+                ("hearts", 10),
+                ("spades", 11),
+                ("clubs", 12),
+                ("hearts", 13),
+                ("hearts", 1)
+            ]),
+            Some(HandRank::Straight)
+        );
+        assert_eq!(
+            hand(&[
+                ("hearts", 12),
+                ("spades", 13),
+                ("clubs", 1),
+                ("hearts", 2),
+                ("hearts", 3)
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn every_card_must_count_towards_the_hand() {
+        assert_eq!(hand(&[("hearts", 7)]), None);
+        assert_eq!(hand(&[("hearts", 7), ("hearts", 8)]), None);
+        assert_eq!(hand(&[("hearts", 7), ("spades", 7), ("clubs", 8)]), None);
+        assert_eq!(
+            hand(&[("hearts", 7), ("spades", 7), ("clubs", 8), ("clubs", 9)]),
+            None
+        );
+        assert_eq!(
+            hand(&[
+                ("hearts", 7),
+                ("spades", 7),
+                ("clubs", 2),
+                ("hearts", 2),
+                ("hearts", 9)
+            ]),
+            None
+        );
+        assert_eq!(hand(&[]), None);
+    }
+
+    #[test]
+    fn rewards_follow_the_hand_table() {
+        let table = [
+            (HandRank::Pair, (1, 0, 0, 0)),
+            (HandRank::ThreeOfAKind, (2, 0, 0, 0)),
+            (HandRank::Straight, (2, 3, 0, 0)),
+            (HandRank::Flush, (2, 0, 2, 2)),
+            (HandRank::FullHouse, (3, 3, 0, 0)),
+            (HandRank::FourOfAKind, (4, 0, 2, 2)),
+            (HandRank::StraightFlush, (5, 5, 3, 3)),
+        ];
+        for (rank, (tanks, gold, defense_buff, buff_turns)) in table {
+            assert_eq!(
+                rank.reward(),
+                HandReward {
+                    tanks,
+                    gold,
+                    defense_buff,
+                    buff_turns
+                }
+            );
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use std::fmt;
+
+// ------------------------------------------------------------
+// Errors
+// ------------------------------------------------------------
+
+/// Every way a player's action can be rejected.
+///
+/// Each variant has a stable numeric code (see [`GameError::code`]) that is
+/// written to the `action_result` table, so clients can branch on failures
+/// without matching on message text. Codes must never be reused or renumbered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameError {
+    // Games and seats
+    GameNotFound,
+    GameConfigNotFound,
+    InvalidGameName,
+    GameOver,
+    InvalidColor,
+    AlreadyInGame,
+    ColorTaken,
+    PlayerNotFound,
+    PlayerEliminated,
+    NotYourTurn { current_color: String },
+    InvalidGameConfig { reason: String },
+    ReplayMismatch { difference: String },
+    NoOtherPlayers,
+
+    // Tiles
+    TileNotFound,
+    SourceTileNotFound,
+    DestinationTileNotFound,
+    TileNotOwned,
+    CannotAttackOwnTile,
+    SameTile,
+    TilesNotAdjacent,
+    TileImpassable,
+
+    // Units and resources
+    NoUnitsCommitted,
+    NotEnoughTroops,
+    NotEnoughTanks,
+    MustKeepTroop,
+    NotEnoughGold,
+    NotEnoughStamina,
+
+    // Cards
+    WrongCardCount,
+    CardNotFound,
+    CardNotOwned,
+    CardsNotPair,
+    InvalidHand,
+    DuplicateCard,
+    HandLimitExceeded { max_hand_size: u32 },
+    NoCardsSelected,
+}
+
+impl GameError {
+    /// Returns the stable code identifying this error.
+    pub fn code(&self) -> u16 {
+        match self {
+            GameError::GameNotFound => 100,
+            GameError::GameConfigNotFound => 101,
+            GameError::InvalidGameName => 102,
+            GameError::GameOver => 103,
+            GameError::InvalidColor => 104,
+            GameError::AlreadyInGame => 105,
+            GameError::ColorTaken => 106,
+            GameError::PlayerNotFound => 107,
+            GameError::PlayerEliminated => 108,
+            GameError::NotYourTurn { .. } => 109,
+            GameError::InvalidGameConfig { .. } => 110,
+            GameError::ReplayMismatch { .. } => 111,
+            GameError::NoOtherPlayers => 112,
+
+            GameError::TileNotFound => 200,
+            GameError::SourceTileNotFound => 201,
+            GameError::DestinationTileNotFound => 202,
+            // 203 is retired (tiles are looked up on the caller's board, see `find_tile()`)
+            GameError::TileNotOwned => 204,
+            GameError::CannotAttackOwnTile => 205,
+            GameError::SameTile => 206,
+            GameError::TilesNotAdjacent => 207,
+            GameError::TileImpassable => 208,
+
+            GameError::NoUnitsCommitted => 300,
+            GameError::NotEnoughTroops => 301,
+            GameError::NotEnoughTanks => 302,
+            GameError::MustKeepTroop => 303,
+            GameError::NotEnoughGold => 304,
+            GameError::NotEnoughStamina => 305,
+
+            GameError::WrongCardCount => 400,
+            GameError::CardNotFound => 401,
+            GameError::CardNotOwned => 402,
+            GameError::CardsNotPair => 403,
+            GameError::InvalidHand => 404,
+            GameError::DuplicateCard => 405,
+            GameError::HandLimitExceeded { .. } => 406,
+            GameError::NoCardsSelected => 407,
+        }
+    }
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameError::GameNotFound => write!(f, "Game not found"),
+            GameError::GameConfigNotFound => write!(f, "Game config not found"),
+            GameError::InvalidGameName => write!(f, "Game name must not be empty"),
+            GameError::GameOver => write!(f, "The game is over"),
+            GameError::InvalidColor => write!(f, "Invalid player color"),
+            GameError::AlreadyInGame => write!(f, "You are already seated in a game"),
+            GameError::ColorTaken => write!(f, "Color is already taken"),
+            GameError::PlayerNotFound => write!(f, "Player not found"),
+            GameError::PlayerEliminated => write!(f, "You have been eliminated"),
+            GameError::NotYourTurn { current_color } => {
+                write!(f, "It is not your turn (current player: {})", current_color)
+            }
+            GameError::InvalidGameConfig { reason } => write!(f, "Invalid game config: {}", reason),
+            GameError::ReplayMismatch { difference } => {
+                write!(
+                    f,
+                    "Replaying the game gave a different result: {}",
+                    difference
+                )
+            }
+            GameError::NoOtherPlayers => {
+                write!(f, "There is no other player to pass the turn to")
+            }
+
+            GameError::TileNotFound => write!(f, "Tile not found"),
+            GameError::SourceTileNotFound => write!(f, "Source tile not found"),
+            GameError::DestinationTileNotFound => write!(f, "Destination tile not found"),
+            GameError::TileNotOwned => write!(f, "You don't own this tile"),
+            GameError::CannotAttackOwnTile => write!(f, "You cannot attack your own tile"),
+            GameError::SameTile => write!(f, "Source and destination must be different tiles"),
+            GameError::TilesNotAdjacent => write!(f, "Tiles must be adjacent"),
+            GameError::TileImpassable => write!(f, "Units cannot enter this tile"),
+
+            GameError::NoUnitsCommitted => write!(f, "You must commit at least one troop or tank"),
+            GameError::NotEnoughTroops => write!(f, "Not enough troops"),
+            GameError::NotEnoughTanks => write!(f, "Not enough tanks"),
+            GameError::MustKeepTroop => write!(f, "Source tile must keep at least 1 troop"),
+            GameError::NotEnoughGold => write!(f, "Not enough gold"),
+            GameError::NotEnoughStamina => write!(f, "Not enough stamina"),
+
+            GameError::WrongCardCount => write!(f, "Must provide exactly two cards"),
+            GameError::CardNotFound => write!(f, "Card not found"),
+            GameError::CardNotOwned => write!(f, "You don't own all of these cards"),
+            GameError::CardsNotPair => write!(f, "Cards must be a pair (same number)"),
+            GameError::InvalidHand => write!(f, "Cards don't form a playable hand"),
+            GameError::DuplicateCard => write!(f, "The same card was given more than once"),
+            GameError::HandLimitExceeded { max_hand_size } => write!(
+                f,
+                "You must play or discard cards down to {} first",
+                max_hand_size
+            ),
+            GameError::NoCardsSelected => write!(f, "You must select at least one card"),
+        }
+    }
+}
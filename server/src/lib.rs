@@ -1,28 +1,675 @@
-use log;
+mod board;
+mod cards;
+mod error;
+mod replay;
+mod rules;
+mod terrain;
+
+use board::{Board, Topology};
+use cards::{evaluate_hand, HandRank};
+use error::GameError;
 use rand::Rng;
-use spacetimedb::{reducer, Identity, ReducerContext, Table};
+use replay::{replay, CardFace, CardsAction, GameActionKind, TileAction, UnitsAction};
+use rules::{
+    active_defense_buff, apply_build_infantry, apply_build_tank, apply_hand_gold,
+    apply_hand_reward, apply_move, award_turn_start, board_tile_id, calculate_income, check_attack,
+    check_build_infantry, check_move, check_pass_turn, check_turn, deal_cards,
+    discard_over_hand_limit, evaluate_victory, is_pair, new_board, new_deck, new_seats, next_seat,
+    next_turn_number, resolve_attack, spend_stamina, tiles_in_sight, turn_rng,
+    validate_game_config, AttackOutcome,
+};
+use spacetimedb::{
+    reducer, view, Identity, ReducerContext, Table, TimeDuration, Timestamp, ViewContext,
+};
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
+use terrain::Terrain;
 
 // ------------------------------------------------------------
 // Constants
 // ------------------------------------------------------------
-const PLAYER_COLORS: &[&str] = &["red", "green", "yellow", "orange", "purple"];
-const BOARD_SIZE: u32 = 7;
-const NATURAL_DEFENSE: u32 = 1;
+// Seat colors in turn order, a game with N players uses the first N
+const PLAYER_PALETTE: &[&str] = &[
+    "red", "green", "yellow", "orange", "purple", "blue", "pink", "cyan",
+];
+const MIN_PLAYERS: u32 = 2;
+
+// Default board and seats written to each new game's config
+const DEFAULT_PLAYER_COUNT: u32 = 5;
+const DEFAULT_TOPOLOGY: Topology = Topology::Rectangular;
+const DEFAULT_BOARD_WIDTH: u32 = 7;
+const DEFAULT_BOARD_HEIGHT: u32 = 7;
+const DEFAULT_NATURAL_DEFENSE: u32 = 1;
+const DEFAULT_STARTING_TROOPS: u32 = 5;
+
+// Boards must fit a full table of start positions, and stay small enough to build in one reducer call
+const MIN_BOARD_SIZE: u32 = 5;
+const MAX_BOARD_SIZE: u32 = 64;
+
+// Upper limits on custom rules, which keep combat, income, victory and timer arithmetic from overflowing
+const MAX_TANK_POWER: u32 = 100;
+const MAX_VICTORY_TERRITORY_PERCENT: u32 = 100;
+const MAX_TURN_LIMIT: u64 = 10_000;
+const MAX_TURN_DURATION_SECS: u64 = 86_400;
+const MAX_OFFLINE_GRACE_SECS: u64 = 86_400;
+const MAX_NATURAL_DEFENSE: u32 = 1_000;
+const MAX_STARTING_TROOPS: u32 = 1_000;
+const MAX_GOLD_PER_TURN: u32 = 100;
+const MAX_GOLD_MINE_INCOME: u32 = 10;
+const MAX_REGION_BONUS_GOLD: u32 = 10;
+const MAX_STAMINA_PER_TURN: u32 = 100;
+const MAX_STAMINA_CAP: u32 = 100;
+
+// Default economy written to each new game's config
+const DEFAULT_GOLD_PER_TURN: u32 = 2;
+const DEFAULT_CARDS_PER_TURN: u32 = 2;
+const DEFAULT_MAX_HAND_SIZE: u32 = 7;
+const DEFAULT_INFANTRY_GOLD_COST: u32 = 1;
+const DEFAULT_GOLD_MINE_INCOME: u32 = 1;
+
+// Default territory income written to each new game's config
+const DEFAULT_TILES_PER_GOLD: u32 = 3;
+const DEFAULT_REGION_BONUS_SIZE: u32 = 5;
+const DEFAULT_REGION_BONUS_GOLD: u32 = 2;
+const DEFAULT_TANK_POWER: u32 = 2;
+
+// Default stamina rules written to each new game's config
+const DEFAULT_MAX_STAMINA: u32 = 2;
+const DEFAULT_STAMINA_PER_TURN: u32 = 1;
+const DEFAULT_ATTACK_STAMINA_COST: u32 = 1;
+const DEFAULT_MOVE_STAMINA_COST: u32 = 1;
+
+// Default victory rules written to each new game's config
+const DEFAULT_VICTORY_TERRITORY_PERCENT: u32 = 60;
+const DEFAULT_TURN_LIMIT: u64 = 100;
+
+// How long each player's turn lasts by default
+const DEFAULT_TURN_DURATION_SECS: u64 = 5;
+
+// Offline players keep their turns for this long before they are skipped
+const DEFAULT_OFFLINE_GRACE_SECS: u64 = 30;
+
+// Attacks are orthogonal-only unless a game's config enables diagonals
+const DEFAULT_ALLOW_DIAGONAL_ATTACKS: bool = false;
+
+// Every player sees the whole board unless a game's config enables fog of war
+const DEFAULT_FOG_OF_WAR: bool = false;
+
+// Random draws made on each seat's turn, each from its own stream of the game's seed
+const DEAL_STREAM: u64 = 0;
+const HAND_LIMIT_STREAM: u64 = 1;
 
 // ------------------------------------------------------------
 // Helper Functions
 // ------------------------------------------------------------
-fn create_new_deck(ctx: &ReducerContext) {
-    let suits = ["hearts", "diamonds", "clubs", "spades"];
-    for suit in suits.iter() {
-        for value in 1..=13 {
-            ctx.db.card().insert(Card {
-                card_id: 0,
-                suit: suit.to_string(),
-                value,
-                owner_color: None, // Cards start in the deck
-            });
+
+/// Records the outcome of a player's action in the caller's `action_result` row.
+///
+/// A reducer that returns an error has its whole transaction rolled back, which
+/// would also discard the recorded failure. Player actions therefore run in
+/// `try_*` functions that validate everything before writing, and their
+/// reducers report the result here and return normally so the row is committed.
+fn record_action_result(ctx: &ReducerContext, action: &str, result: Result<(), GameError>) {
+    let (error_code, error_message) = match result {
+        Ok(()) => (None, None),
+        Err(err) => {
+            log::warn!(
+                "{} rejected for {}: [{}] {}",
+                action,
+                ctx.sender,
+                err.code(),
+                err
+            );
+            (Some(err.code()), Some(err.to_string()))
+        }
+    };
+
+    ctx.db.action_result().identity().delete(ctx.sender);
+    ctx.db.action_result().insert(ActionResult {
+        identity: ctx.sender,
+        action: action.to_string(),
+        error_code,
+        error_message,
+        timestamp: ctx.timestamp,
+    });
+}
+/// Loads every card of a game, in `card_id` order so random draws over them are repeatable.
+fn game_cards(ctx: &ReducerContext, game_id: u64) -> Vec<Card> {
+    let mut cards: Vec<Card> = ctx.db.card().game_id().filter(game_id).collect();
+    cards.sort_by_key(|card| card.card_id);
+    cards
+}
+
+/// Writes back the cards whose owner or pile changed since they were loaded with [`game_cards`].
+fn save_changed_cards(ctx: &ReducerContext, before: &[Card], after: &[Card]) {
+    for (old, new) in before.iter().zip(after) {
+        if old.owner_color != new.owner_color || old.discarded != new.discarded {
+            ctx.db.card().card_id().update(new.clone());
+        }
+    }
+}
+
+/// Finds the seat held by the caller through their session.
+///
+/// An identity holds at most one seat, so this also determines which game the
+/// caller is acting in.
+fn find_caller(ctx: &ReducerContext) -> Result<Player, GameError> {
+    let session = ctx
+        .db
+        .player_session()
+        .identity()
+        .find(ctx.sender)
+        .ok_or(GameError::PlayerNotFound)?;
+    ctx.db
+        .player()
+        .player_id()
+        .find(session.player_id)
+        .ok_or(GameError::PlayerNotFound)
+}
+
+/// Derives a tile's ID from its game and coordinates.
+///
+/// Tile IDs are deterministic, so any tile can be looked up directly from its
+/// coordinates, and since the ID is the primary key each game can only ever
+/// have one tile per coordinate. Coordinates must be on the game's board, since
+/// larger ones would spill into another column or game, so look tiles up from
+/// player input with [`find_tile`].
+fn tile_id(game_id: u64, x: u32, y: u32) -> u64 {
+    (game_id << 32) | ((x as u64) << 16) | y as u64
+}
+
+/// Looks up a tile of a game by its coordinates.
+///
+/// # Returns
+/// * `Option<Tile>` - The tile, or None if the coordinates are off the game's board
+fn find_tile(ctx: &ReducerContext, game_id: u64, x: u32, y: u32) -> Option<Tile> {
+    let config = ctx.db.game_config().game_id().find(game_id)?;
+    let tile_id = board_tile_id(&config, game_id, x, y)?;
+    ctx.db
+        .tile()
+        .tile_id()
+        .find(tile_id)
+        .filter(|tile| tile.game_id == game_id)
+}
+
+/// Builds the config a game gets when it is created without custom rules.
+fn default_game_config() -> GameConfig {
+    GameConfig {
+        game_id: 0,
+        max_stamina: DEFAULT_MAX_STAMINA,
+        stamina_per_turn: DEFAULT_STAMINA_PER_TURN,
+        attack_stamina_cost: DEFAULT_ATTACK_STAMINA_COST,
+        move_stamina_cost: DEFAULT_MOVE_STAMINA_COST,
+        victory_territory_percent: DEFAULT_VICTORY_TERRITORY_PERCENT,
+        turn_limit: DEFAULT_TURN_LIMIT,
+        allow_diagonal_attacks: DEFAULT_ALLOW_DIAGONAL_ATTACKS,
+        fog_of_war: DEFAULT_FOG_OF_WAR,
+        offline_grace_secs: DEFAULT_OFFLINE_GRACE_SECS,
+        turn_duration_secs: DEFAULT_TURN_DURATION_SECS,
+        topology: DEFAULT_TOPOLOGY,
+        board_width: DEFAULT_BOARD_WIDTH,
+        board_height: DEFAULT_BOARD_HEIGHT,
+        natural_defense: DEFAULT_NATURAL_DEFENSE,
+        player_count: DEFAULT_PLAYER_COUNT,
+        starting_troops: DEFAULT_STARTING_TROOPS,
+        gold_per_turn: DEFAULT_GOLD_PER_TURN,
+        cards_per_turn: DEFAULT_CARDS_PER_TURN,
+        max_hand_size: DEFAULT_MAX_HAND_SIZE,
+        infantry_gold_cost: DEFAULT_INFANTRY_GOLD_COST,
+        gold_mine_income: DEFAULT_GOLD_MINE_INCOME,
+        tiles_per_gold: DEFAULT_TILES_PER_GOLD,
+        region_bonus_size: DEFAULT_REGION_BONUS_SIZE,
+        region_bonus_gold: DEFAULT_REGION_BONUS_GOLD,
+        tank_power: DEFAULT_TANK_POWER,
+    }
+}
+
+/// Returns the color of the seat at the given position in the turn order.
+fn seat_color(seat: u32) -> &'static str {
+    PLAYER_PALETTE[seat as usize]
+}
+
+/// Verifies the player's game is still running and it is their turn to act.
+///
+/// Games in simultaneous mode let every player act at any time.
+///
+/// # Returns
+/// * `Game` - The player's game
+fn ensure_players_turn(ctx: &ReducerContext, player: &Player) -> Result<Game, GameError> {
+    let game = ctx
+        .db
+        .game()
+        .game_id()
+        .find(player.game_id)
+        .ok_or(GameError::GameNotFound)?;
+    check_turn(&game, player, game.simultaneous)?;
+    Ok(game)
+}
+
+/// Looks up the cards a player wants to spend, verifying they hold every one of them.
+///
+/// # Returns
+/// * `Vec<Card>` - The cards, in the order they were given
+fn find_owned_cards(
+    ctx: &ReducerContext,
+    player: &Player,
+    card_ids: &[u32],
+) -> Result<Vec<Card>, GameError> {
+    let mut cards = Vec::with_capacity(card_ids.len());
+    for (i, &card_id) in card_ids.iter().enumerate() {
+        if card_ids[..i].contains(&card_id) {
+            return Err(GameError::DuplicateCard);
+        }
+        let card = ctx
+            .db
+            .card()
+            .card_id()
+            .find(card_id)
+            .ok_or(GameError::CardNotFound)?;
+        if card.game_id != player.game_id || card.owner_color.as_ref() != Some(&player.color) {
+            return Err(GameError::CardNotOwned);
+        }
+        cards.push(card);
+    }
+    Ok(cards)
+}
+
+/// Counts the cards in a seat's hand.
+fn hand_size(ctx: &ReducerContext, game_id: u64, color: &str) -> u32 {
+    ctx.db
+        .card()
+        .game_id()
+        .filter(game_id)
+        .filter(|card| card.owner_color.as_deref() == Some(color))
+        .count() as u32
+}
+
+/// Moves a card from a player's hand to the discard pile.
+fn discard_card(ctx: &ReducerContext, card: Card) {
+    ctx.db.card().card_id().update(Card {
+        owner_color: None,
+        discarded: true,
+        ..card
+    });
+}
+
+/// Discards cards at random from a seat's hand until it is within the game's hand size limit.
+///
+/// Players over the limit are expected to discard themselves, this only runs
+/// once their turn is over.
+fn enforce_hand_limit(ctx: &ReducerContext, config: &GameConfig, game: &Game, seat: u32) {
+    let color = seat_color(seat);
+    let before = game_cards(ctx, config.game_id);
+    let mut cards = before.clone();
+    let mut rng = turn_rng(game.rng_seed, game.turn, seat, HAND_LIMIT_STREAM);
+    let discarded = discard_over_hand_limit(&mut cards, color, config.max_hand_size, &mut rng);
+    save_changed_cards(ctx, &before, &cards);
+    if discarded.is_empty() {
+        return;
+    }
+
+    for &i in &discarded {
+        log::info!(
+            "Player {} is over the hand size limit, discarding card {}",
+            color,
+            cards[i].card_id
+        );
+    }
+    record_event(
+        ctx,
+        config.game_id,
+        Some(color),
+        GameEventKind::CardsDiscarded(discarded.len() as u32),
+    );
+}
+
+/// Adds an accepted action to its game's action log.
+///
+/// Together with the game's seed, the log is everything [`replay`] needs to
+/// rebuild the game, so every reducer that changes a game's tiles, players or
+/// cards logs what it did.
+fn log_action(ctx: &ReducerContext, game_id: u64, color: &str, kind: GameActionKind) {
+    ctx.db.game_action().insert(GameAction {
+        action_id: 0,
+        game_id,
+        color: color.to_string(),
+        kind,
+        timestamp: ctx.timestamp,
+    });
+}
+
+/// Adds an event to its game's event log, stamped with the game's current turn.
+///
+/// Every event is recorded, and `visible_events` hides the ones that would reveal
+/// units out of the caller's sight in fog of war games.
+fn record_event(ctx: &ReducerContext, game_id: u64, color: Option<&str>, kind: GameEventKind) {
+    let Some(game) = ctx.db.game().game_id().find(game_id) else {
+        return;
+    };
+    ctx.db.game_event().insert(GameEvent {
+        event_id: 0,
+        game_id,
+        turn: game.turn,
+        color: color.map(str::to_string),
+        kind,
+        timestamp: ctx.timestamp,
+    });
+}
+
+/// Records what each seat can currently see in a fog of war game.
+///
+/// Tiles in sight always show their live units, so this only has to run after
+/// the board or turn changes for tiles that later drop out of sight to keep
+/// showing the units last seen on them.
+fn refresh_sightings(ctx: &ReducerContext, game_id: u64) {
+    let Some(config) = ctx.db.game_config().game_id().find(game_id) else {
+        return;
+    };
+    if !config.fog_of_war {
+        return;
+    }
+    let Some(game) = ctx.db.game().game_id().find(game_id) else {
+        return;
+    };
+
+    let tiles: Vec<Tile> = ctx.db.tile().game_id().filter(game_id).collect();
+    for player in ctx.db.player().game_id().filter(game_id) {
+        let in_sight = tiles_in_sight(&config, &tiles, &player.color);
+        let mut known: HashMap<u64, TileSighting> = ctx
+            .db
+            .tile_sighting()
+            .player_id()
+            .filter(player.player_id)
+            .map(|sighting| (sighting.tile_id, sighting))
+            .collect();
+        for tile in tiles.iter().filter(|t| in_sight.contains(&(t.x, t.y))) {
+            let sighting = TileSighting {
+                sighting_id: 0,
+                player_id: player.player_id,
+                tile_id: tile.tile_id,
+                troops: tile.troops,
+                tanks: tile.tanks,
+                turn: game.turn,
+            };
+            match known.remove(&tile.tile_id) {
+                Some(old)
+                    if (old.troops, old.tanks, old.turn)
+                        == (tile.troops, tile.tanks, game.turn) => {}
+                Some(old) => {
+                    ctx.db.tile_sighting().sighting_id().update(TileSighting {
+                        sighting_id: old.sighting_id,
+                        ..sighting
+                    });
+                }
+                None => {
+                    ctx.db.tile_sighting().insert(sighting);
+                }
+            }
+        }
+    }
+}
+
+/// Applies the game's victory conditions (see [`evaluate_victory`]) after the
+/// board or turn counter changes, marking eliminated players and finishing the
+/// game if it is over. Finishing the game stops its turn timer.
+///
+/// # Returns
+/// * `bool` - true if the game is over
+fn check_victory(ctx: &ReducerContext, game_id: u64) -> bool {
+    let Some(game) = ctx.db.game().game_id().find(game_id) else {
+        return true;
+    };
+    if game.status != GameStatus::InProgress {
+        return true;
+    }
+    let Some(config) = ctx.db.game_config().game_id().find(game_id) else {
+        return false;
+    };
+
+    let tiles: Vec<Tile> = ctx.db.tile().game_id().filter(game_id).collect();
+    let players: Vec<Player> = ctx.db.player().game_id().filter(game_id).collect();
+    let outcome = evaluate_victory(&config, game.turn, &tiles, &players);
+    for player in players
+        .into_iter()
+        .filter(|p| outcome.eliminated.contains(&p.color))
+    {
+        log::info!(
+            "Game {}: player {} has been eliminated",
+            game_id,
+            player.color
+        );
+        record_event(
+            ctx,
+            game_id,
+            Some(&player.color),
+            GameEventKind::PlayerEliminated,
+        );
+        ctx.db.player().player_id().update(Player {
+            eliminated: true,
+            ..player
+        });
+    }
+    if !outcome.finished {
+        return false;
+    }
+
+    let winner = outcome.winner;
+    log::info!(
+        "Game {} is over! Winner: {}",
+        game_id,
+        winner.as_deref().unwrap_or("none")
+    );
+    record_event(ctx, game_id, winner.as_deref(), GameEventKind::GameOver);
+    ctx.db.game().game_id().update(Game {
+        status: GameStatus::Finished,
+        winner,
+        ..game
+    });
+
+    // Stop advancing the finished game's turns
+    ctx.db.next_turn_timer().game_id().delete(game_id);
+    true
+}
+
+/// Schedules the game's next turn change, replacing any pending one.
+///
+/// Turn timers are one-shot, so every turn change schedules the next one. This
+/// keeps the deadline in step with turns that were ended early.
+fn schedule_next_turn(ctx: &ReducerContext, game_id: u64, deadline: Timestamp) {
+    ctx.db.next_turn_timer().game_id().delete(game_id);
+    ctx.db.next_turn_timer().insert(NextTurnTimer {
+        scheduled_id: 0,
+        scheduled_at: spacetimedb::ScheduleAt::Time(deadline),
+        game_id,
+    });
+}
+
+/// Passes the turn to the next player who can take one and sets its deadline.
+fn advance_turn(ctx: &ReducerContext, game: Game) {
+    let game_id = game.game_id;
+    let Some(config) = ctx.db.game_config().game_id().find(game_id) else {
+        log::warn!("Game {} has no config, not advancing turns", game_id);
+        return;
+    };
+
+    // Move to the next player who can take a turn, skipping unclaimed seats,
+    // eliminated players and players who have been offline for longer than the
+    // grace period
+    let players: Vec<Player> = ctx.db.player().game_id().filter(game_id).collect();
+    let next_index = next_seat(&config, game.current_player_index, &players, ctx.timestamp);
+    let turn_duration = TimeDuration::from(Duration::from_secs(config.turn_duration_secs));
+    let Some(current_index) = next_index else {
+        // Nobody can play yet, so check again later without advancing the turn counter
+        let turn_deadline = ctx.timestamp + turn_duration;
+        schedule_next_turn(ctx, game_id, turn_deadline);
+        ctx.db.game().game_id().update(Game {
+            turn_deadline,
+            ..game
+        });
+        return;
+    };
+    log_action(
+        ctx,
+        game_id,
+        seat_color(current_index),
+        GameActionKind::AdvanceTurn(current_index),
+    );
+
+    // The turn is over, so the outgoing player can no longer hold on to extra cards
+    enforce_hand_limit(ctx, &config, &game, game.current_player_index);
+
+    // If we've gone through all players, increment the turn number
+    let current_turn = next_turn_number(game.turn, game.current_player_index, current_index);
+    if current_turn > game.turn {
+        log::info!("Game {}: turn {} has begun!", game_id, current_turn);
+    }
+
+    // Update the game state
+    let turn_deadline = ctx.timestamp + turn_duration;
+    ctx.db.game().game_id().update(Game {
+        current_player_index: current_index,
+        turn: current_turn,
+        turn_deadline,
+        ..game
+    });
+
+    // End the game if the turn limit has been passed
+    if check_victory(ctx, game_id) {
+        return;
+    }
+
+    // Schedule the end of the new turn
+    schedule_next_turn(ctx, game_id, turn_deadline);
+
+    // Get the new current player's color
+    let current_color = seat_color(current_index);
+    log::info!(
+        "Game {}: current player: {} (index: {})",
+        game_id,
+        current_color,
+        current_index
+    );
+
+    // Start the new current player's turn
+    start_player_turn(ctx, game_id, current_index);
+
+    // Record what each seat can see on the new turn
+    refresh_sightings(ctx, game_id);
+}
+
+/// Recalculates the income breakdown of every seat in a game after its territory changes.
+fn refresh_income(ctx: &ReducerContext, game_id: u64) {
+    let Some(config) = ctx.db.game_config().game_id().find(game_id) else {
+        return;
+    };
+    let tiles: Vec<Tile> = ctx.db.tile().game_id().filter(game_id).collect();
+    for player in ctx.db.player().game_id().filter(game_id) {
+        let income = calculate_income(&config, &tiles, &player);
+        ctx.db.player_income().player_id().delete(player.player_id);
+        ctx.db.player_income().insert(income);
+    }
+}
+
+/// Starts a player's turn by awarding their per-turn gold, stamina and cards.
+fn start_player_turn(ctx: &ReducerContext, game_id: u64, seat: u32) {
+    let (Some(config), Some(game)) = (
+        ctx.db.game_config().game_id().find(game_id),
+        ctx.db.game().game_id().find(game_id),
+    ) else {
+        log::warn!("Game {} has no config, skipping turn rewards", game_id);
+        return;
+    };
+    let current_color = seat_color(seat);
+    record_event(
+        ctx,
+        game_id,
+        Some(current_color),
+        GameEventKind::TurnStarted,
+    );
+
+    // Award the seat's income and the configured stamina to the current player if the seat is claimed
+    refresh_income(ctx, game_id);
+    if let Some(player) = ctx
+        .db
+        .player()
+        .game_id()
+        .filter(game_id)
+        .find(|p| p.color == current_color && p.identity.is_some())
+    {
+        let player_color = player.color.clone();
+        let income = ctx
+            .db
+            .player_income()
+            .player_id()
+            .find(player.player_id)
+            .map_or(0, |income| income.total);
+        let awarded = award_turn_start(&config, player, income);
+        let (new_gold, new_stamina) = (awarded.gold, awarded.stamina);
+
+        // Update player's gold and stamina
+        ctx.db.player().player_id().update(awarded);
+        log::info!(
+            "Awarded {} gold and {} stamina to player {}. New totals: gold={}, stamina={}",
+            income,
+            config.stamina_per_turn,
+            player_color,
+            new_gold,
+            new_stamina
+        );
+        record_event(
+            ctx,
+            game_id,
+            Some(&player_color),
+            GameEventKind::IncomeAwarded(IncomeEvent {
+                gold: income,
+                stamina: config.stamina_per_turn,
+            }),
+        );
+
+        // Deal the configured number of cards to the current player
+        let before = game_cards(ctx, game_id);
+        let mut cards = before.clone();
+        let mut rng = turn_rng(game.rng_seed, game.turn, seat, DEAL_STREAM);
+        let dealt = deal_cards(&mut cards, &player_color, config.cards_per_turn, &mut rng);
+        save_changed_cards(ctx, &before, &cards);
+
+        // Log the dealt cards
+        for &i in &dealt {
+            let value_str = match cards[i].value {
+                1 => "Ace",
+                2 => "2",
+                3 => "3",
+                4 => "4",
+                5 => "5",
+                6 => "6",
+                7 => "7",
+                8 => "8",
+                9 => "9",
+                10 => "10",
+                11 => "Jack",
+                12 => "Queen",
+                13 => "King",
+                _ => "Unknown",
+            };
+            log::info!(
+                "Dealt {} of {} to player {}",
+                value_str,
+                cards[i].suit,
+                player_color
+            );
+        }
+        if (dealt.len() as u32) < config.cards_per_turn {
+            log::info!("No cards left to deal to player {}", player_color);
+        }
+
+        // Only the number of cards is public, the cards themselves stay in the hand
+        if !dealt.is_empty() {
+            record_event(
+                ctx,
+                game_id,
+                Some(&player_color),
+                GameEventKind::CardsDealt(dealt.len() as u32),
+            );
         }
     }
 }
@@ -31,36 +678,112 @@ fn create_new_deck(ctx: &ReducerContext) {
 // Schemas
 // ------------------------------------------------------------
 
-#[spacetimedb::table(name = tile, public)]
+// Tiles are private so fog of war can't be read around, clients read them through the `visible_tiles` view
+#[spacetimedb::table(name = tile)]
 #[derive(Clone)]
 pub struct Tile {
     #[primary_key]
-    #[auto_inc]
-    tile_id: u32,
+    tile_id: u64, // Derived from the game and coordinates, see `tile_id()`
+    #[index(btree)]
+    game_id: u64, // The game this tile belongs to
     x: u32,
     y: u32,
+    terrain: Terrain,            // Affects defense, passability and income
     owner_color: Option<String>, // None means neutral
     troops: u32,                 // Number of infantry troops on this tile
     tanks: u32,                  // Number of tanks on this tile
+    defense_buff: u32,           // Temporary defense from played cards
+    buff_until_turn: u64,        // Last turn the defense buff applies
 }
 
 #[spacetimedb::table(name = player, public)]
 #[derive(Clone)]
 pub struct Player {
     #[primary_key]
+    #[auto_inc]
+    player_id: u32,
+    #[index(btree)]
+    game_id: u64, // The game this seat belongs to
     color: String,
     identity: Option<Identity>,
     online: bool,
-    gold: u32,    // Amount of gold the player has
-    stamina: u32, // Amount of stamina the player has (max 2)
+    gold: u32,                    // Amount of gold the player has
+    stamina: u32,                 // Amount of stamina the player has (capped by the game's config)
+    eliminated: bool,             // True once the player owns no tiles
+    last_seen: Option<Timestamp>, // When the seat's holder last joined, connected or disconnected
+}
+
+// Maps each identity to the one seat it holds, so callers are resolved by index
+#[spacetimedb::table(name = player_session, public)]
+pub struct PlayerSession {
+    #[primary_key]
+    identity: Identity,
+    #[unique]
+    player_id: u32, // The seat held by this identity
+    game_id: u64, // The game that seat belongs to
+}
+
+#[derive(spacetimedb::SpacetimeType, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameStatus {
+    InProgress,
+    Finished,
 }
 
 #[spacetimedb::table(name = game, public)]
 pub struct Game {
     #[primary_key]
+    #[auto_inc]
+    game_id: u64,
     game_name: String,
     turn: u64,
     current_player_index: u32,
+    simultaneous: bool, // When true, every player may act at any time instead of only on their turn
+    status: GameStatus,
+    winner: Option<String>, // Color of the winning player once the game is finished
+    turn_deadline: Timestamp, // When the current turn ends if the player doesn't end it early
+    rng_seed: u64, // Seeds the board's terrain and every random draw, so the game can be replayed
+}
+
+#[spacetimedb::table(name = game_config, public)]
+pub struct GameConfig {
+    #[primary_key]
+    game_id: u64,
+    max_stamina: u32,               // Stamina cap
+    stamina_per_turn: u32,          // Stamina regenerated at the start of each turn
+    attack_stamina_cost: u32,       // Stamina spent per attack
+    move_stamina_cost: u32,         // Stamina spent per move
+    victory_territory_percent: u32, // Share of the board needed to win (0 disables)
+    turn_limit: u64,                // The game ends after this many turns (0 disables)
+    allow_diagonal_attacks: bool,   // Whether attacks may also target diagonal tiles
+    fog_of_war: bool,               // Whether players only see units on and next to their own tiles
+    offline_grace_secs: u64,        // How long offline players keep their turns
+    turn_duration_secs: u64,        // How long each turn lasts before it is passed on
+    topology: Topology,             // Whether the board is made of square or hex tiles
+    board_width: u32,               // Number of tiles along x
+    board_height: u32,              // Number of tiles along y
+    natural_defense: u32,           // Defense of neutral tiles
+    player_count: u32,              // Number of seats, colored from the start of `PLAYER_PALETTE`
+    starting_troops: u32,           // Troops on each seat's base tile
+    gold_per_turn: u32,             // Gold awarded at the start of each turn
+    cards_per_turn: u32,            // Cards dealt at the start of each turn
+    max_hand_size: u32, // Most cards a player may keep once their turn ends (0 disables)
+    infantry_gold_cost: u32, // Gold spent per infantry built
+    tank_power: u32,    // Attack and defense of each tank, and the damage it absorbs
+    gold_mine_income: u32, // Extra gold per owned gold mine at the start of each turn
+    tiles_per_gold: u32, // Tiles owned per extra gold each turn (0 disables)
+    region_bonus_size: u32, // Connected tiles needed for a region bonus (0 disables)
+    region_bonus_gold: u32, // Extra gold per region of at least `region_bonus_size` tiles
+}
+
+impl GameConfig {
+    /// Returns the shape of the game's board.
+    fn board(&self) -> Board {
+        Board {
+            topology: self.topology,
+            width: self.board_width,
+            height: self.board_height,
+        }
+    }
 }
 
 #[spacetimedb::table(name = next_turn_timer, scheduled(next_turn))]
@@ -69,110 +792,664 @@ pub struct NextTurnTimer {
     #[auto_inc]
     scheduled_id: u64,
     scheduled_at: spacetimedb::ScheduleAt,
+    #[index(btree)]
+    game_id: u64, // The game whose turns this timer advances
+}
+
+// What each seat last saw of a tile in a fog of war game
+#[spacetimedb::table(name = tile_sighting)]
+pub struct TileSighting {
+    #[primary_key]
+    #[auto_inc]
+    sighting_id: u64,
+    #[index(btree)]
+    player_id: u32, // The seat that saw the tile
+    tile_id: u64,
+    troops: u32,
+    tanks: u32,
+    turn: u64, // The last turn the tile was in sight
+}
+
+// Each seat's income for its next turn, so clients can show where its gold comes from
+#[spacetimedb::table(name = player_income, public)]
+pub struct PlayerIncome {
+    #[primary_key]
+    player_id: u32,
+    #[index(btree)]
+    game_id: u64,
+    color: String,
+    tiles_owned: u32,
+    base: u32,         // Flat gold every seat earns
+    territory: u32,    // Gold from the number of tiles owned
+    gold_mines: u32,   // Gold from owned gold mines
+    region_bonus: u32, // Gold from large connected regions
+    total: u32,
+}
+
+// Outcome of every resolved attack, so clients can show combat results.
+// Private since `defense` and the losses show the units on both tiles, clients read it through `visible_combats`.
+#[spacetimedb::table(name = combat_result)]
+pub struct CombatResult {
+    #[primary_key]
+    #[auto_inc]
+    combat_id: u64,
+    #[index(btree)]
+    game_id: u64,
+    attacker_color: String,
+    defender_color: Option<String>, // None if the target tile was neutral
+    from_tile_id: u64,
+    to_tile_id: u64,
+    attack_power: u32,
+    defense: u32,
+    attacker_troops_lost: u32,
+    attacker_tanks_lost: u32,
+    defender_troops_lost: u32,
+    defender_tanks_lost: u32,
+    captured: bool, // True if the attacker took the target tile
+    timestamp: Timestamp,
+}
+
+// Every outcome in a game, in order, so clients can show a feed and history.
+// Private since builds, moves and attacks show units out of sight, clients read it through `visible_events`.
+#[spacetimedb::table(name = game_event)]
+pub struct GameEvent {
+    #[primary_key]
+    #[auto_inc]
+    event_id: u64,
+    #[index(btree)]
+    game_id: u64,
+    turn: u64,             // The game's turn when the event happened
+    color: Option<String>, // The seat the event is about, None for game-wide events
+    kind: GameEventKind,
+    timestamp: Timestamp,
+}
+
+// What happened in a game event, with the details clients need to show it
+#[derive(spacetimedb::SpacetimeType, Clone, Debug)]
+pub enum GameEventKind {
+    GameCreated,
+    PlayerJoined,
+    PlayerLeft,
+    TurnStarted,
+    TurnEnded, // The seat ended its turn early
+    IncomeAwarded(IncomeEvent),
+    CardsDealt(u32),     // Number of cards dealt, the cards themselves stay private
+    CardsDiscarded(u32), // Number of cards discarded, by the player or for being over the hand limit
+    InfantryBuilt(TileEvent),
+    TankBuilt(TileEvent),
+    CardsPlayed(CardsPlayedEvent),
+    UnitsMoved(MoveEvent),
+    AttackResolved(AttackEvent),
+    PlayerEliminated,
+    GameOver, // The event's color is the winner, None if there is none
+}
+
+impl GameEventKind {
+    /// Returns the tiles whose units the event shows, which fog of war hides from
+    /// players without any of them in sight. Other events are seen by everyone.
+    fn unit_tiles(&self) -> Vec<(u32, u32)> {
+        match self {
+            GameEventKind::InfantryBuilt(tile) | GameEventKind::TankBuilt(tile) => {
+                vec![(tile.x, tile.y)]
+            }
+            GameEventKind::CardsPlayed(played) => vec![(played.x, played.y)],
+            GameEventKind::UnitsMoved(moved) => {
+                vec![(moved.from_x, moved.from_y), (moved.to_x, moved.to_y)]
+            }
+            GameEventKind::AttackResolved(attack) => {
+                vec![(attack.from_x, attack.from_y), (attack.to_x, attack.to_y)]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(spacetimedb::SpacetimeType, Clone, Debug)]
+pub struct IncomeEvent {
+    gold: u32,
+    stamina: u32,
+}
+
+#[derive(spacetimedb::SpacetimeType, Clone, Debug)]
+pub struct TileEvent {
+    x: u32,
+    y: u32,
+}
+
+#[derive(spacetimedb::SpacetimeType, Clone, Debug)]
+pub struct CardsPlayedEvent {
+    x: u32,
+    y: u32,
+    hand: HandRank,
+    tanks: u32,
+    gold: u32,
+    defense_buff: u32,
+    buff_turns: u64,
+}
+
+// Only the public part of an attack, its units and losses are in `combat_result`
+#[derive(spacetimedb::SpacetimeType, Clone, Debug)]
+pub struct AttackEvent {
+    combat_id: u64,
+    from_x: u32,
+    from_y: u32,
+    to_x: u32,
+    to_y: u32,
+    captured: bool,
+}
+
+#[derive(spacetimedb::SpacetimeType, Clone, Debug)]
+pub struct MoveEvent {
+    from_x: u32,
+    from_y: u32,
+    to_x: u32,
+    to_y: u32,
+    troops: u32,
+    tanks: u32,
 }
 
-// New tables for card system
-#[spacetimedb::table(name = card, public)]
+// Every accepted action in a game, in order, so the game can be replayed from its seed.
+// Private since the log shows played cards and moves out of sight.
+#[spacetimedb::table(name = game_action)]
+pub struct GameAction {
+    #[primary_key]
+    #[auto_inc]
+    action_id: u64,
+    #[index(btree)]
+    game_id: u64,
+    color: String, // The seat that acted, or whose turn started
+    kind: GameActionKind,
+    timestamp: Timestamp,
+}
+
+// Outcome of each identity's most recent action, so clients can react to rejections.
+// Private so nobody sees another player's rejections, clients read their own through `my_action_result`.
+#[spacetimedb::table(name = action_result)]
+pub struct ActionResult {
+    #[primary_key]
+    identity: Identity,
+    action: String,                // Name of the reducer that was called
+    error_code: Option<u16>,       // Stable `GameError` code, None if the action succeeded
+    error_message: Option<String>, // Human readable reason the action was rejected
+    timestamp: Timestamp,
+}
+
+// New tables for card system. Cards are private so nobody can see the deck or
+// another player's hand, clients read them through the `my_cards` and `hand_sizes` views.
+#[spacetimedb::table(name = card)]
 #[derive(Clone)]
 pub struct Card {
     #[primary_key]
     #[auto_inc]
     card_id: u32,
+    #[index(btree)]
+    game_id: u64, // The game whose deck this card belongs to
     suit: String,                // "hearts", "diamonds", "clubs", "spades"
     value: u8,                   // 1-13 (Ace through King)
-    owner_color: Option<String>, // None means card is in deck, Some(player_color) means card belongs to player
+    owner_color: Option<String>, // None means card is in the deck or discard pile, Some(player_color) means card belongs to player
+    discarded: bool,             // True while the card is in the discard pile
+}
+
+// A tile as the caller sees it, with its units hidden when it's out of sight in a fog of war game
+#[derive(spacetimedb::SpacetimeType)]
+pub struct VisibleTile {
+    tile_id: u64,
+    game_id: u64,
+    x: u32,
+    y: u32,
+    terrain: Terrain,
+    owner_color: Option<String>,
+    troops: Option<u32>,       // None if the caller has never seen the tile
+    tanks: Option<u32>,        // None if the caller has never seen the tile
+    defense_buff: Option<u32>, // The active buff from played cards, None while out of sight
+    in_sight: bool,            // False if the units are from the caller's last sighting
+    seen_turn: Option<u64>,    // The turn the units were seen on, None if never seen
+}
+
+// Number of cards in a seat's hand, which is all opponents get to know about it
+#[derive(spacetimedb::SpacetimeType)]
+pub struct HandSize {
+    game_id: u64,
+    color: String,
+    cards: u32,
+}
+
+// ------------------------------------------------------------
+// Views
+// ------------------------------------------------------------
+
+/// Returns the board of the caller's game as the caller sees it.
+///
+/// In fog of war games, units are only shown on tiles the caller owns and the
+/// tiles next to them, while other tiles show what the caller last saw of them.
+/// The whole board is revealed once the game is over.
+#[view(name = visible_tiles, public)]
+fn visible_tiles(ctx: &ViewContext) -> Vec<VisibleTile> {
+    let Some(session) = ctx.db.player_session().identity().find(ctx.sender) else {
+        return Vec::new();
+    };
+    let (Some(player), Some(game), Some(config)) = (
+        ctx.db.player().player_id().find(session.player_id),
+        ctx.db.game().game_id().find(session.game_id),
+        ctx.db.game_config().game_id().find(session.game_id),
+    ) else {
+        return Vec::new();
+    };
+
+    let tiles: Vec<Tile> = ctx.db.tile().game_id().filter(game.game_id).collect();
+    let fogged = config.fog_of_war && game.status == GameStatus::InProgress;
+    let in_sight = if fogged {
+        tiles_in_sight(&config, &tiles, &player.color)
+    } else {
+        HashSet::new()
+    };
+    let sightings: HashMap<u64, TileSighting> = if fogged {
+        ctx.db
+            .tile_sighting()
+            .player_id()
+            .filter(player.player_id)
+            .map(|sighting| (sighting.tile_id, sighting))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    tiles
+        .into_iter()
+        .map(|tile| {
+            let visible = !fogged || in_sight.contains(&(tile.x, tile.y));
+            let (troops, tanks, defense_buff, seen_turn) = if visible {
+                (
+                    Some(tile.troops),
+                    Some(tile.tanks),
+                    Some(active_defense_buff(&tile, game.turn)),
+                    Some(game.turn),
+                )
+            } else if let Some(sighting) = sightings.get(&tile.tile_id) {
+                (
+                    Some(sighting.troops),
+                    Some(sighting.tanks),
+                    None,
+                    Some(sighting.turn),
+                )
+            } else {
+                (None, None, None, None)
+            };
+            VisibleTile {
+                tile_id: tile.tile_id,
+                game_id: tile.game_id,
+                x: tile.x,
+                y: tile.y,
+                terrain: tile.terrain,
+                owner_color: tile.owner_color,
+                troops,
+                tanks,
+                defense_buff,
+                in_sight: visible,
+                seen_turn,
+            }
+        })
+        .collect()
 }
 
-#[spacetimedb::reducer(init)]
-pub fn init(ctx: &ReducerContext) {
-    // Initialize the game with turn 1
-    ctx.db.game().insert(Game {
-        game_name: "main".to_string(),
+/// Returns the outcome of the attacks in the caller's game.
+///
+/// In fog of war games, only attacks the caller made or defended against are
+/// shown, since an attack's defense and losses give away the units on both tiles.
+/// Every attack is shown once the game is over.
+#[view(name = visible_combats, public)]
+fn visible_combats(ctx: &ViewContext) -> Vec<CombatResult> {
+    let Some(session) = ctx.db.player_session().identity().find(ctx.sender) else {
+        return Vec::new();
+    };
+    let (Some(player), Some(game), Some(config)) = (
+        ctx.db.player().player_id().find(session.player_id),
+        ctx.db.game().game_id().find(session.game_id),
+        ctx.db.game_config().game_id().find(session.game_id),
+    ) else {
+        return Vec::new();
+    };
+
+    let fogged = config.fog_of_war && game.status == GameStatus::InProgress;
+    ctx.db
+        .combat_result()
+        .game_id()
+        .filter(game.game_id)
+        .filter(|combat| {
+            !fogged
+                || combat.attacker_color == player.color
+                || combat.defender_color.as_ref() == Some(&player.color)
+        })
+        .collect()
+}
+
+/// Returns the event log of the caller's game.
+///
+/// In fog of war games, events showing units are only shown when they are the
+/// caller's own or when one of their tiles is in the caller's sight. The whole
+/// log is shown once the game is over.
+#[view(name = visible_events, public)]
+fn visible_events(ctx: &ViewContext) -> Vec<GameEvent> {
+    let Some(session) = ctx.db.player_session().identity().find(ctx.sender) else {
+        return Vec::new();
+    };
+    let (Some(player), Some(game), Some(config)) = (
+        ctx.db.player().player_id().find(session.player_id),
+        ctx.db.game().game_id().find(session.game_id),
+        ctx.db.game_config().game_id().find(session.game_id),
+    ) else {
+        return Vec::new();
+    };
+
+    let fogged = config.fog_of_war && game.status == GameStatus::InProgress;
+    let in_sight = if fogged {
+        let tiles: Vec<Tile> = ctx.db.tile().game_id().filter(game.game_id).collect();
+        tiles_in_sight(&config, &tiles, &player.color)
+    } else {
+        HashSet::new()
+    };
+    ctx.db
+        .game_event()
+        .game_id()
+        .filter(game.game_id)
+        .filter(|event| {
+            let tiles = event.kind.unit_tiles();
+            !fogged
+                || tiles.is_empty()
+                || event.color.as_ref() == Some(&player.color)
+                || tiles.iter().any(|tile| in_sight.contains(tile))
+        })
+        .collect()
+}
+
+/// Returns the outcome of the caller's most recent action.
+#[view(name = my_action_result, public)]
+fn my_action_result(ctx: &ViewContext) -> Option<ActionResult> {
+    ctx.db.action_result().identity().find(ctx.sender)
+}
+
+/// Returns the cards in the caller's own hand.
+#[view(name = my_cards, public)]
+fn my_cards(ctx: &ViewContext) -> Vec<Card> {
+    let Some(session) = ctx.db.player_session().identity().find(ctx.sender) else {
+        return Vec::new();
+    };
+    let Some(player) = ctx.db.player().player_id().find(session.player_id) else {
+        return Vec::new();
+    };
+    ctx.db
+        .card()
+        .game_id()
+        .filter(player.game_id)
+        .filter(|card| card.owner_color.as_ref() == Some(&player.color))
+        .collect()
+}
+
+/// Returns how many cards every seat in the caller's game holds, without revealing which cards they are.
+#[view(name = hand_sizes, public)]
+fn hand_sizes(ctx: &ViewContext) -> Vec<HandSize> {
+    let Some(session) = ctx.db.player_session().identity().find(ctx.sender) else {
+        return Vec::new();
+    };
+    ctx.db
+        .player()
+        .game_id()
+        .filter(session.game_id)
+        .map(|player| HandSize {
+            game_id: player.game_id,
+            cards: ctx
+                .db
+                .card()
+                .game_id()
+                .filter(player.game_id)
+                .filter(|card| card.owner_color.as_ref() == Some(&player.color))
+                .count() as u32,
+            color: player.color,
+        })
+        .collect()
+}
+
+/// Creates a new game instance with its own board, seats, deck and turn timer.
+///
+/// # Arguments
+/// * `ctx` - The reducer context containing database access and sender information
+/// * `game_name` - A display name for the game
+/// * `simultaneous` - If true, all players may act at any time instead of taking turns
+///
+/// # Returns
+/// * Nothing, the outcome is written to the caller's `action_result` row
+#[reducer]
+pub fn create_game(ctx: &ReducerContext, game_name: String, simultaneous: bool) {
+    let result = try_create_game(ctx, game_name, simultaneous, default_game_config());
+    record_action_result(ctx, "create_game", result);
+}
+
+/// Creates a new game that plays by custom rules instead of the defaults.
+///
+/// # Arguments
+/// * `ctx` - The reducer context containing database access and sender information
+/// * `game_name` - A display name for the game
+/// * `simultaneous` - If true, all players may act at any time instead of taking turns
+/// * `config` - The game's rules (its `game_id` is ignored and assigned on creation)
+///
+/// # Returns
+/// * Nothing, the outcome is written to the caller's `action_result` row
+///
+/// # Config Rules
+/// See [`validate_game_config`]
+#[reducer]
+pub fn create_custom_game(
+    ctx: &ReducerContext,
+    game_name: String,
+    simultaneous: bool,
+    config: GameConfig,
+) {
+    let result = try_create_game(ctx, game_name, simultaneous, config);
+    record_action_result(ctx, "create_custom_game", result);
+}
+
+/// Validates and performs [`create_game`] and [`create_custom_game`], returning why it was rejected if it wasn't allowed.
+fn try_create_game(
+    ctx: &ReducerContext,
+    game_name: String,
+    simultaneous: bool,
+    config: GameConfig,
+) -> Result<(), GameError> {
+    // Step 1: Verify the game has a name and playable rules
+    let game_name = game_name.trim().to_string();
+    if game_name.is_empty() {
+        return Err(GameError::InvalidGameName);
+    }
+    validate_game_config(&config)?;
+
+    // Step 2: Initialize the game with turn 1
+    let turn_deadline =
+        ctx.timestamp + TimeDuration::from(Duration::from_secs(config.turn_duration_secs));
+    let game = ctx.db.game().insert(Game {
+        game_id: 0,
+        game_name,
         turn: 1,
         current_player_index: 0,
+        simultaneous,
+        status: GameStatus::InProgress,
+        winner: None,
+        turn_deadline,
+        rng_seed: ctx.rng().gen(),
     });
+    let game_id = game.game_id;
 
-    // Initialize the 5 players with their colors
-    for color in PLAYER_COLORS {
-        ctx.db.player().insert(Player {
-            color: color.to_string(),
-            identity: None,
-            online: false,
-            gold: 0,    // Initialize gold to 0
-            stamina: 0, // Initialize stamina to 0
-        });
+    // Step 3: Store the game's rules
+    let config = ctx
+        .db
+        .game_config()
+        .insert(GameConfig { game_id, ..config });
+
+    record_event(ctx, game_id, None, GameEventKind::GameCreated);
+
+    // Step 4: Initialize a player seat for each configured player
+    for player in new_seats(game_id, &config) {
+        ctx.db.player().insert(player);
     }
 
-    // Initialize the board with tiles
-    for x in 0..BOARD_SIZE {
-        for y in 0..BOARD_SIZE {
-            // Initialize all tiles with natural defense
-            let mut tile = Tile {
-                tile_id: 0,
-                x,
-                y,
-                owner_color: None,
-                troops: 0,
-                tanks: 0,
-            };
+    // Step 5: Initialize the board with tiles on a map generated from the game's seed
+    for tile in new_board(game_id, &config, game.rng_seed) {
+        ctx.db.tile().insert(tile);
+    }
 
-            // Set initial player positions with 5 troops
-            match (x, y) {
-                (1, 1) => {
-                    tile.owner_color = Some("red".to_string());
-                    tile.troops = 5;
-                }
-                (5, 1) => {
-                    tile.owner_color = Some("green".to_string());
-                    tile.troops = 5;
-                }
-                (1, 5) => {
-                    tile.owner_color = Some("yellow".to_string());
-                    tile.troops = 5;
-                }
-                (5, 5) => {
-                    tile.owner_color = Some("orange".to_string());
-                    tile.troops = 5;
-                }
-                (3, 3) => {
-                    tile.owner_color = Some("purple".to_string());
-                    tile.troops = 5;
-                }
-                _ => {}
-            }
+    // Step 6: Set up the timer to end the first turn
+    schedule_next_turn(ctx, game_id, turn_deadline);
+
+    // Step 7: Initialize the deck of cards
+    for card in new_deck(game_id) {
+        ctx.db.card().insert(card);
+    }
+
+    // Step 8: Start the first player's turn
+    start_player_turn(ctx, game_id, 0);
+
+    // Step 9: Record what each seat can see of the starting board
+    refresh_sightings(ctx, game_id);
+
+    log::info!("Created game {} ({})", game.game_name, game_id);
+    Ok(())
+}
+
+/// Claims a color's seat in a game for the caller.
+///
+/// # Arguments
+/// * `ctx` - The reducer context containing database access and sender information
+/// * `game_id` - The ID of the game to join
+/// * `color` - The color of the seat to claim
+///
+/// # Returns
+/// * Nothing, the outcome is written to the caller's `action_result` row
+///
+/// # Join Rules
+/// 1. The game must exist and still be in progress
+/// 2. The color must be one of the game's player colors, not yet claimed and not eliminated
+/// 3. The caller must not already be seated in a game
+#[reducer]
+pub fn join_game(ctx: &ReducerContext, game_id: u64, color: String) {
+    let result = try_join_game(ctx, game_id, color);
+    record_action_result(ctx, "join_game", result);
+}
+
+/// Validates and performs [`join_game`], returning why it was rejected if it wasn't allowed.
+fn try_join_game(ctx: &ReducerContext, game_id: u64, color: String) -> Result<(), GameError> {
+    // Step 1: Check if the game exists and is still running
+    let game = ctx
+        .db
+        .game()
+        .game_id()
+        .find(game_id)
+        .ok_or(GameError::GameNotFound)?;
+    if game.status != GameStatus::InProgress {
+        return Err(GameError::GameOver);
+    }
+
+    // Step 2: Check if the caller is already seated in a game, since reducers
+    // resolve the caller's game from their seat
+    if ctx
+        .db
+        .player_session()
+        .identity()
+        .find(ctx.sender)
+        .is_some()
+    {
+        return Err(GameError::AlreadyInGame);
+    }
 
-            ctx.db.tile().insert(tile);
-        }
+    // Step 3: Check if the color is one of this game's seats, still free and still in the game
+    let player = ctx
+        .db
+        .player()
+        .game_id()
+        .filter(game_id)
+        .find(|p| p.color == color)
+        .ok_or(GameError::InvalidColor)?;
+    if player.identity.is_some() {
+        return Err(GameError::ColorTaken);
+    }
+    if player.eliminated {
+        return Err(GameError::PlayerEliminated);
     }
 
-    // Set up the timer to increment turns every 10 seconds
-    ctx.db.next_turn_timer().insert(NextTurnTimer {
-        scheduled_id: 0,
-        scheduled_at: spacetimedb::ScheduleAt::Interval(Duration::from_secs(5).into()),
+    // Step 4: Record the caller's seat and update the player with the new identity
+    ctx.db.player_session().insert(PlayerSession {
+        identity: ctx.sender,
+        player_id: player.player_id,
+        game_id,
     });
-
-    // Initialize the deck of cards
-    create_new_deck(ctx);
+    record_event(
+        ctx,
+        game_id,
+        Some(&player.color),
+        GameEventKind::PlayerJoined,
+    );
+    log_action(
+        ctx,
+        game_id,
+        &player.color,
+        GameActionKind::JoinGame(ctx.sender),
+    );
+    ctx.db.player().player_id().update(Player {
+        identity: Some(ctx.sender),
+        online: true,
+        last_seen: Some(ctx.timestamp),
+        ..player
+    });
+    Ok(())
 }
+
+/// Releases the caller's seat so its color can be claimed by someone else.
+///
+/// The seat keeps its tiles, gold, stamina and cards for whoever claims it next,
+/// and the caller is free to join another game.
+///
+/// # Returns
+/// * Nothing, the outcome is written to the caller's `action_result` row
 #[reducer]
-pub fn join_game(ctx: &ReducerContext, color: String) {
-    // First check if the color is valid
-    if !PLAYER_COLORS.contains(&color.as_str()) {
-        return;
-    }
+pub fn leave_game(ctx: &ReducerContext) {
+    let result = try_leave_game(ctx);
+    record_action_result(ctx, "leave_game", result);
+}
 
-    // Check if the color is already assigned
-    if let Some(player) = ctx.db.player().color().find(&color) {
-        if player.identity.is_some() {
-            return; // Color is already assigned
-        }
-    }
+/// Validates and performs [`leave_game`], returning why it was rejected if it wasn't allowed.
+fn try_leave_game(ctx: &ReducerContext) -> Result<(), GameError> {
+    // Step 1: Get the caller's seat
+    let player = find_caller(ctx)?;
+
+    // Step 2: Remove the caller's session and free the seat
+    ctx.db.player_session().identity().delete(ctx.sender);
+    log::info!("Player {} left game {}", player.color, player.game_id);
+    record_event(
+        ctx,
+        player.game_id,
+        Some(&player.color),
+        GameEventKind::PlayerLeft,
+    );
+    log_action(
+        ctx,
+        player.game_id,
+        &player.color,
+        GameActionKind::LeaveGame,
+    );
+    ctx.db.player().player_id().update(Player {
+        identity: None,
+        online: false,
+        last_seen: Some(ctx.timestamp),
+        ..player
+    });
+    Ok(())
+}
 
-    // Update the player with the new identity
-    if let Some(player) = ctx.db.player().color().find(&color) {
-        ctx.db.player().color().update(Player {
-            identity: Some(ctx.sender),
+#[reducer(client_connected)]
+pub fn client_connected(ctx: &ReducerContext) {
+    // Find the player with this identity and mark them as online again
+    if let Ok(player) = find_caller(ctx) {
+        ctx.db.player().player_id().update(Player {
             online: true,
+            last_seen: Some(ctx.timestamp),
             ..player
         });
     }
@@ -181,174 +1458,136 @@ pub fn join_game(ctx: &ReducerContext, color: String) {
 #[reducer(client_disconnected)]
 pub fn client_disconnected(ctx: &ReducerContext) {
     // Find the player with this identity and mark them as offline
-    for player in ctx.db.player().iter() {
-        if player.identity == Some(ctx.sender) {
-            ctx.db.player().color().update(Player {
-                online: false,
-                ..player
-            });
-            break;
-        }
+    if let Ok(player) = find_caller(ctx) {
+        ctx.db.player().player_id().update(Player {
+            online: false,
+            last_seen: Some(ctx.timestamp),
+            ..player
+        });
     }
 }
 
 #[reducer]
-pub fn next_turn(ctx: &ReducerContext, _timer: NextTurnTimer) {
-    // Get the current game state
-    if let Some(game) = ctx.db.game().iter().next() {
-        let mut current_index = game.current_player_index;
-        let mut current_turn = game.turn;
-
-        // Get the current player's color
-        let current_color = PLAYER_COLORS[current_index as usize];
-        log::info!(
-            "Current player: {} (index: {})",
-            current_color,
-            current_index
-        );
-
-        // Award 2 gold and 1 stamina to the current player if they are online
-        if let Some(player) = ctx.db.player().color().find(&current_color.to_string()) {
-            let player_color = player.color.clone();
-            let new_gold = player.gold + 2;
-            let new_stamina = (player.stamina + 1).min(2); // Cap at 2
-
-            // Update player's gold and stamina
-            ctx.db.player().color().update(Player {
-                gold: new_gold,
-                stamina: new_stamina,
-                ..player.clone()
-            });
-            log::info!(
-                "Awarded 2 gold and 1 stamina to player {}. New totals: gold={}, stamina={}",
-                player_color,
-                new_gold,
-                new_stamina
-            );
+pub fn next_turn(ctx: &ReducerContext, timer: NextTurnTimer) {
+    // Only the scheduler may advance turns, players pass with end_turn
+    if ctx.sender != ctx.identity() {
+        return;
+    }
 
-            // Deal 2 cards to the current player if they are online
-            for _ in 0..2 {
-                // Get available cards from the deck
-                let mut available_cards: Vec<Card> = ctx
-                    .db
-                    .card()
-                    .iter()
-                    .filter(|card| card.owner_color.is_none())
-                    .collect();
-
-                // If deck is empty, create a new one
-                if available_cards.is_empty() {
-                    log::info!("Deck depleted, creating new deck...");
-                    // Delete all cards that are in the deck (owner_color is None)
-                    for card in ctx
-                        .db
-                        .card()
-                        .iter()
-                        .filter(|card| card.owner_color.is_none())
-                    {
-                        ctx.db.card().card_id().delete(&card.card_id);
-                    }
-                    // Create a new deck of cards
-                    create_new_deck(ctx);
-                    // Get the newly created cards
-                    available_cards = ctx
-                        .db
-                        .card()
-                        .iter()
-                        .filter(|card| card.owner_color.is_none())
-                        .collect();
-                }
+    // Get the state of the game this timer belongs to
+    let Some(game) = ctx.db.game().game_id().find(timer.game_id) else {
+        return;
+    };
+    if game.status != GameStatus::InProgress {
+        return;
+    }
 
-                // Now we know we have cards available
-                let card = available_cards
-                    .get(ctx.rng().gen_range(0..available_cards.len()))
-                    .unwrap();
-                let mut card = (*card).clone();
-                let suit = card.suit.clone();
-                let value = card.value;
-                let player_color = player.color.clone();
-
-                card.owner_color = Some(player_color.clone());
-                ctx.db.card().card_id().update(card);
-
-                // Log the dealt card
-                let value_str = match value {
-                    1 => "Ace",
-                    2 => "2",
-                    3 => "3",
-                    4 => "4",
-                    5 => "5",
-                    6 => "6",
-                    7 => "7",
-                    8 => "8",
-                    9 => "9",
-                    10 => "10",
-                    11 => "Jack",
-                    12 => "Queen",
-                    13 => "King",
-                    _ => "Unknown",
-                };
-                log::info!("Dealt {} of {} to player {}", value_str, suit, player_color);
-            }
-        }
+    advance_turn(ctx, game);
+}
 
-        // Move to the next player (we have 5 players total)
-        current_index = (current_index + 1) % PLAYER_COLORS.len() as u32;
+/// Ends the caller's turn early, passing it to the next player.
+///
+/// # Returns
+/// * Nothing, the outcome is written to the caller's `action_result` row
+///
+/// # End Turn Rules
+/// 1. The game must still be in progress
+/// 2. The caller must not have been eliminated
+/// 3. It must be the caller's turn, even in simultaneous games
+/// 4. Another seat must be able to take the turn, so a lone player can't start new turns at will
+#[reducer]
+pub fn end_turn(ctx: &ReducerContext) {
+    let result = try_end_turn(ctx);
+    record_action_result(ctx, "end_turn", result);
+}
 
-        // If we've gone through all players, increment the turn number
-        if current_index == 0 {
-            current_turn += 1;
-            log::info!("Turn {} has begun!", current_turn);
-        }
+/// Validates and performs [`end_turn`], returning why it was rejected if it wasn't allowed.
+fn try_end_turn(ctx: &ReducerContext) -> Result<(), GameError> {
+    // Step 1: Get the current player
+    let player = find_caller(ctx)?;
 
-        // Update the game state
-        ctx.db.game().game_name().update(Game {
-            game_name: "main".to_string(),
-            current_player_index: current_index,
-            turn: current_turn,
-        });
-    }
+    // Step 2: Verify the game is running and it is the player's turn
+    let game = ctx
+        .db
+        .game()
+        .game_id()
+        .find(player.game_id)
+        .ok_or(GameError::GameNotFound)?;
+    check_turn(&game, &player, false)?;
+
+    // Step 3: Verify there is another seat to pass the turn to
+    let config = ctx
+        .db
+        .game_config()
+        .game_id()
+        .find(player.game_id)
+        .ok_or(GameError::GameConfigNotFound)?;
+    let players: Vec<Player> = ctx.db.player().game_id().filter(player.game_id).collect();
+    check_pass_turn(&config, game.current_player_index, &players, ctx.timestamp)?;
+
+    // Step 4: Pass the turn on
+    log::info!("Player {} ended their turn early", player.color);
+    record_event(
+        ctx,
+        player.game_id,
+        Some(&player.color),
+        GameEventKind::TurnEnded,
+    );
+    advance_turn(ctx, game);
+    Ok(())
 }
 
 #[reducer]
-pub fn build_infantry(ctx: &ReducerContext, x: u32, y: u32) -> Result<(), String> {
+pub fn build_infantry(ctx: &ReducerContext, x: u32, y: u32) {
+    let result = try_build_infantry(ctx, x, y);
+    record_action_result(ctx, "build_infantry", result);
+}
+
+/// Validates and performs [`build_infantry`], returning why it was rejected if it wasn't allowed.
+fn try_build_infantry(ctx: &ReducerContext, x: u32, y: u32) -> Result<(), GameError> {
     // Get the current player
-    let player = ctx
-        .db
-        .player()
-        .iter()
-        .find(|p| p.identity == Some(ctx.sender))
-        .ok_or("Player not found")?;
+    let player = find_caller(ctx)?;
     let player_color = player.color.clone();
 
-    // Get the target tile
-    let tile = ctx
-        .db
-        .tile()
-        .iter()
-        .find(|t| t.x == x && t.y == y)
-        .ok_or("Tile not found")?;
+    // Check it is the player's turn
+    ensure_players_turn(ctx, &player)?;
 
-    // Check if player owns the tile
-    if tile.owner_color.as_ref() != Some(&player_color) {
-        return Err("You don't own this tile".into());
-    }
+    // Get the target tile in the player's game
+    let tile = find_tile(ctx, player.game_id, x, y).ok_or(GameError::TileNotFound)?;
 
-    // Check if player has enough gold
-    if player.gold < 1 {
-        return Err("Not enough gold".into());
-    }
+    // Check if player owns the tile, has discarded down to the hand size limit and has enough gold
+    let config = ctx
+        .db
+        .game_config()
+        .game_id()
+        .find(player.game_id)
+        .ok_or(GameError::GameConfigNotFound)?;
+    check_build_infantry(
+        &config,
+        &player,
+        &tile,
+        hand_size(ctx, player.game_id, &player_color),
+    )?;
 
     // Update player's gold and tile's troops
-    ctx.db.player().color().update(Player {
-        gold: player.gold - 1,
-        ..player
-    });
-
-    ctx.db.tile().tile_id().update(Tile {
-        troops: tile.troops + 1,
-        ..tile
-    });
+    let (player, tile) = apply_build_infantry(&config, player, tile);
+    ctx.db.player().player_id().update(player.clone());
+    ctx.db.tile().tile_id().update(tile.clone());
+
+    // Update what each seat can see of the tile
+    refresh_sightings(ctx, player.game_id);
+    record_event(
+        ctx,
+        player.game_id,
+        Some(&player_color),
+        GameEventKind::InfantryBuilt(TileEvent { x, y }),
+    );
+    log_action(
+        ctx,
+        player.game_id,
+        &player_color,
+        GameActionKind::BuildInfantry(TileAction { x, y }),
+    );
 
     log::info!(
         "BUILD INFANTRY SUCCESS:\n- Player: {}\n- Tile: ({}, {}) [ID: {}]\n- New troop count: {}\n- Remaining gold: {}",
@@ -356,8 +1595,8 @@ pub fn build_infantry(ctx: &ReducerContext, x: u32, y: u32) -> Result<(), String
         x,
         y,
         tile.tile_id,
-        tile.troops + 1,
-        player.gold - 1
+        tile.troops,
+        player.gold
     );
     Ok(())
 }
@@ -366,109 +1605,198 @@ pub fn build_infantry(ctx: &ReducerContext, x: u32, y: u32) -> Result<(), String
 ///
 /// # Arguments
 /// * `ctx` - The reducer context containing database access and sender information
-/// * `from_tile_id` - The ID of the tile initiating the attack (source tile)
-/// * `to_tile_id` - The ID of the tile being attacked (target tile)
+/// * `from_x` - The x coordinate of the tile initiating the attack (source tile)
+/// * `from_y` - The y coordinate of the source tile
+/// * `to_x` - The x coordinate of the tile being attacked (target tile)
+/// * `to_y` - The y coordinate of the target tile
+/// * `troops` - Number of troops from the source tile to commit to the attack
+/// * `tanks` - Number of tanks from the source tile to commit to the attack
 ///
 /// # Returns
-/// * `Result<(), String>` - Ok(()) if the attack succeeds, Err with message if it fails
+/// * Nothing, the outcome is written to the caller's `action_result` row (a lost battle is not a failure)
 ///
 /// # Attack Rules
-/// 1. Source tile must be owned by the attacking player
-/// 2. Source tile must have at least 2 troops
-/// 3. Attack power must be greater than defense
-/// 4. For unowned tiles, defense = NATURAL_DEFENSE + troops
-/// 5. For owned tiles, defense = troops only
-/// 6. After successful attack:
-///    - Source tile keeps 1 troop
-///    - Target tile is captured and gets remaining troops
+/// 1. It must be the attacking player's turn (unless the game is simultaneous)
+/// 2. Source and target must be different tiles on the attacking player's board
+/// 3. Source tile must be owned by the attacking player, target tile must not be, and must be passable
+/// 4. Tiles must be adjacent (orthogonally, or also diagonally if the game's config allows it)
+/// 5. At least one troop or tank must be committed
+/// 6. Source tile must have enough troops and tanks, and keep at least 1 troop behind
+/// 7. Player must be within the game's hand size limit and have at least the game's attack stamina cost
+/// 8. Attack power = committed troops + (committed tanks * tank power)
+/// 9. For unowned tiles, defense = the game's natural defense + the terrain's defense bonus
+/// 10. For owned tiles, defense = troops + (tanks * tank power) + the terrain's defense bonus + any active card buff
+///
+/// # Combat Resolution
+/// 1. The attacker takes damage equal to the defense, the defender takes damage equal to the attack power
+/// 2. Tanks absorb damage first (tank power each), remaining damage hits troops
+/// 3. If attack power is greater than defense, the target tile is captured and receives the surviving committed units
+/// 4. Otherwise the surviving committed units return to the source tile and the defender keeps the tile
+/// 5. Units that weren't committed stay on the source tile, and the attack stamina cost is spent either way
+/// 6. The outcome is recorded in the `combat_result` table
 #[spacetimedb::reducer]
-pub fn attack(ctx: &ReducerContext, from_tile_id: u32, to_tile_id: u32) -> Result<(), String> {
-    // Step 1: Retrieve the source and target tiles from the database
-    let from_tile = ctx
-        .db
-        .tile()
-        .tile_id()
-        .find(&from_tile_id)
-        .ok_or("Source tile not found")?;
-    let to_tile = ctx
-        .db
-        .tile()
-        .tile_id()
-        .find(&to_tile_id)
-        .ok_or("Destination tile not found")?;
+pub fn attack(
+    ctx: &ReducerContext,
+    from_x: u32,
+    from_y: u32,
+    to_x: u32,
+    to_y: u32,
+    troops: u32,
+    tanks: u32,
+) {
+    let result = try_attack(ctx, from_x, from_y, to_x, to_y, troops, tanks);
+    record_action_result(ctx, "attack", result);
+}
+
+/// Validates and performs [`attack`], returning why it was rejected if it wasn't allowed.
+fn try_attack(
+    ctx: &ReducerContext,
+    from_x: u32,
+    from_y: u32,
+    to_x: u32,
+    to_y: u32,
+    troops: u32,
+    tanks: u32,
+) -> Result<(), GameError> {
+    // Step 1: Verify the attack targets a different tile
+    if (from_x, from_y) == (to_x, to_y) {
+        return Err(GameError::SameTile);
+    }
 
     // Step 2: Get the current player's information
-    let player = ctx
-        .db
-        .player()
-        .iter()
-        .find(|p| p.identity == Some(ctx.sender))
-        .ok_or("Player not found")?;
+    let player = find_caller(ctx)?;
     let player_color = player.color.clone();
 
-    // Step 3: Verify the attacking player owns the source tile
-    if from_tile.owner_color.as_ref() != Some(&player_color) {
-        return Err("You can only attack from your own tiles".to_string());
-    }
+    // Step 3: Verify it is the player's turn
+    let game = ensure_players_turn(ctx, &player)?;
 
-    // Step 4: Verify the source tile has enough troops to attack
-    // Must have at least 2 troops (1 to leave behind, 1 to attack with)
-    if from_tile.troops <= 1 {
-        return Err("You need at least 2 troops to attack".to_string());
-    }
+    // Step 4: Retrieve the source and target tiles in the player's game
+    let from_tile =
+        find_tile(ctx, player.game_id, from_x, from_y).ok_or(GameError::SourceTileNotFound)?;
+    let to_tile =
+        find_tile(ctx, player.game_id, to_x, to_y).ok_or(GameError::DestinationTileNotFound)?;
 
-    // Step 5: Calculate attack power and defense
-    let attack_power = from_tile.troops + (from_tile.tanks * 2);
-    let defense = if to_tile.owner_color.is_none() {
-        NATURAL_DEFENSE
-    } else {
-        to_tile.troops + (to_tile.tanks * 2)
-    };
+    // Step 5: Verify the attack follows the rules
+    let config = ctx
+        .db
+        .game_config()
+        .game_id()
+        .find(player.game_id)
+        .ok_or(GameError::GameConfigNotFound)?;
+    check_attack(
+        &config,
+        &player,
+        &from_tile,
+        &to_tile,
+        troops,
+        tanks,
+        hand_size(ctx, player.game_id, &player_color),
+    )?;
+
+    // Step 6: Work out the outcome of the attack
+    let AttackOutcome {
+        from_tile: attacked_from,
+        to_tile: attacked_to,
+        attack_power,
+        defense,
+        attacker_troops_lost,
+        attacker_tanks_lost,
+        defender_troops_lost,
+        defender_tanks_lost,
+        captured,
+    } = resolve_attack(&config, &from_tile, &to_tile, troops, tanks, game.turn);
+
+    // Step 7: Apply the outcome to both tiles
+    ctx.db.tile().tile_id().update(attacked_from);
+    ctx.db.tile().tile_id().update(attacked_to);
+
+    // Step 8: Record the outcome so clients can display it
+    let combat = ctx.db.combat_result().insert(CombatResult {
+        combat_id: 0,
+        game_id: player.game_id,
+        attacker_color: player_color.clone(),
+        defender_color: to_tile.owner_color.clone(),
+        from_tile_id: from_tile.tile_id,
+        to_tile_id: to_tile.tile_id,
+        attack_power,
+        defense,
+        attacker_troops_lost,
+        attacker_tanks_lost,
+        defender_troops_lost,
+        defender_tanks_lost,
+        captured,
+        timestamp: ctx.timestamp,
+    });
+    record_event(
+        ctx,
+        player.game_id,
+        Some(&player_color),
+        GameEventKind::AttackResolved(AttackEvent {
+            combat_id: combat.combat_id,
+            from_x,
+            from_y,
+            to_x,
+            to_y,
+            captured,
+        }),
+    );
 
-    // Step 6: Verify the attack is strong enough to succeed
-    if attack_power <= defense {
-        return Err("Attack power must be greater than defense".to_string());
+    // Step 9: Spend the attacking player's stamina
+    let game_id = player.game_id;
+    ctx.db
+        .player()
+        .player_id()
+        .update(spend_stamina(player, config.attack_stamina_cost));
+
+    // Step 10: Check whether the capture eliminated a player or won the game,
+    // and update the income of both sides
+    if captured {
+        check_victory(ctx, game_id);
+        refresh_income(ctx, game_id);
     }
 
-    // Step 7: Calculate troops to move
-    // Leave 1 troop in source tile, move the rest to target tile
-    let troops_to_move = from_tile.troops - 1;
-    let tanks_to_move = from_tile.tanks;
-
-    // Step 8: Update the source tile to leave 1 troop behind
-    ctx.db.tile().tile_id().update(Tile {
-        troops: 1, // Leave 1 troop in source tile
-        tanks: 0,  // Move all tanks
-        ..from_tile
-    });
-
-    // Step 9: Update the target tile with new owner and moved troops
-    ctx.db.tile().tile_id().update(Tile {
-        owner_color: Some(player_color.clone()),
-        troops: troops_to_move,
-        tanks: tanks_to_move,
-        ..to_tile
-    });
+    // Step 11: Update what each seat can see of both tiles
+    refresh_sightings(ctx, game_id);
+    log_action(
+        ctx,
+        game_id,
+        &player_color,
+        GameActionKind::Attack(UnitsAction {
+            from_x,
+            from_y,
+            to_x,
+            to_y,
+            troops,
+            tanks,
+        }),
+    );
 
     log::info!(
-        "ATTACK SUCCESS:\n- Attacker: {}\n- From: ({}, {}) [ID: {}]\n  - Starting troops: {}\n  - Starting tanks: {}\n  - Troops moved: {}\n  - Tanks moved: {}\n  - Remaining troops: 1\n  - Remaining tanks: 0\n- To: ({}, {}) [ID: {}]\n  - Previous owner: {}\n  - Previous troops: {}\n  - Previous tanks: {}\n  - New owner: {}\n  - Captured troops: {}\n  - Captured tanks: {}\n- Combat Stats:\n  - Attack Power: {}\n  - Defense: {}\n  - Attack Efficiency: {:.2}%",
+        "ATTACK {}:\n- Attacker: {}\n- From: ({}, {}) [ID: {}]\n  - Starting troops: {}\n  - Starting tanks: {}\n  - Troops committed: {}\n  - Tanks committed: {}\n  - Troops lost: {}\n  - Tanks lost: {}\n- To: ({}, {}) [ID: {}]\n  - Previous owner: {}\n  - Previous troops: {}\n  - Previous tanks: {}\n  - Troops lost: {}\n  - Tanks lost: {}\n  - Owner: {}\n- Combat Stats:\n  - Attack Power: {}\n  - Defense: {}\n  - Attack Efficiency: {:.2}%",
+        if captured { "SUCCESS" } else { "FAILED" },
         player_color,
         from_tile.x,
         from_tile.y,
-        from_tile_id,
+        from_tile.tile_id,
         from_tile.troops,
         from_tile.tanks,
-        troops_to_move,
-        tanks_to_move,
+        troops,
+        tanks,
+        attacker_troops_lost,
+        attacker_tanks_lost,
         to_tile.x,
         to_tile.y,
-        to_tile_id,
+        to_tile.tile_id,
         to_tile.owner_color.as_deref().unwrap_or("neutral"),
         to_tile.troops,
         to_tile.tanks,
-        player_color,
-        troops_to_move,
-        tanks_to_move,
+        defender_troops_lost,
+        defender_tanks_lost,
+        if captured {
+            player_color.as_str()
+        } else {
+            to_tile.owner_color.as_deref().unwrap_or("neutral")
+        },
         attack_power,
         defense,
         (attack_power as f32 / (attack_power + defense) as f32) * 100.0
@@ -486,178 +1814,467 @@ pub fn attack(ctx: &ReducerContext, from_tile_id: u32, to_tile_id: u32) -> Resul
 /// * `card_ids` - Vector of exactly two card IDs that must be a pair (same number)
 ///
 /// # Returns
-/// * `Result<(), String>` - Ok(()) if tank is built successfully, Err with message if it fails
+/// * Nothing, the outcome is written to the caller's `action_result` row
 ///
 /// # Build Rules
-/// 1. Target tile must be owned by the player
-/// 2. Must provide exactly two cards
-/// 3. Cards must be a pair (same number)
-/// 4. Player must own both cards
+/// 1. It must be the player's turn (unless the game is simultaneous)
+/// 2. Target tile must be owned by the player
+/// 3. Must provide exactly two different cards
+/// 4. Cards must be a pair (same number)
+/// 5. Player must own both cards
+///
+/// This is the same as playing a pair with [`play_cards`].
 #[spacetimedb::reducer]
-pub fn build_tank(ctx: &ReducerContext, x: u32, y: u32, card_ids: Vec<u32>) -> Result<(), String> {
+pub fn build_tank(ctx: &ReducerContext, x: u32, y: u32, card_ids: Vec<u32>) {
+    let result = try_build_tank(ctx, x, y, card_ids);
+    record_action_result(ctx, "build_tank", result);
+}
+
+/// Validates and performs [`build_tank`], returning why it was rejected if it wasn't allowed.
+fn try_build_tank(
+    ctx: &ReducerContext,
+    x: u32,
+    y: u32,
+    card_ids: Vec<u32>,
+) -> Result<(), GameError> {
     // Step 1: Verify exactly two cards provided
     if card_ids.len() != 2 {
-        return Err("Must provide exactly two cards".to_string());
+        return Err(GameError::WrongCardCount);
     }
 
     // Step 2: Get the current player
-    let player = ctx
-        .db
-        .player()
-        .iter()
-        .find(|p| p.identity == Some(ctx.sender))
-        .ok_or("Player not found")?;
+    let player = find_caller(ctx)?;
     let player_color = player.color.clone();
 
-    // Step 3: Get the target tile
-    let tile = ctx
-        .db
-        .tile()
-        .iter()
-        .find(|t| t.x == x && t.y == y)
-        .ok_or("Tile not found")?;
+    // Step 3: Verify it is the player's turn
+    ensure_players_turn(ctx, &player)?;
 
-    // Step 4: Verify ownership
+    // Step 4: Get the target tile in the player's game
+    let tile = find_tile(ctx, player.game_id, x, y).ok_or(GameError::TileNotFound)?;
+
+    // Step 5: Verify ownership
     if tile.owner_color.as_ref() != Some(&player_color) {
-        return Err("You can only build on your own tiles".to_string());
+        return Err(GameError::TileNotOwned);
     }
 
-    // Step 5: Get both cards and verify ownership
-    let card1 = ctx
-        .db
-        .card()
-        .card_id()
-        .find(&card_ids[0])
-        .ok_or("First card not found")?;
-    let card2 = ctx
-        .db
-        .card()
-        .card_id()
-        .find(&card_ids[1])
-        .ok_or("Second card not found")?;
-
-    // Step 6: Verify card ownership
-    if card1.owner_color.as_ref() != Some(&player_color)
-        || card2.owner_color.as_ref() != Some(&player_color)
-    {
-        return Err("You don't own both cards".to_string());
-    }
+    // Step 6: Get both cards and verify ownership
+    let cards = find_owned_cards(ctx, &player, &card_ids)?;
 
     // Step 7: Verify cards are a pair (same number)
-    if card1.value != card2.value {
-        return Err("Cards must be a pair (same number)".to_string());
+    if !is_pair(&cards) {
+        return Err(GameError::CardsNotPair);
     }
 
-    // Step 8: Delete both cards
-    ctx.db.card().card_id().delete(&card_ids[0]);
-    ctx.db.card().card_id().delete(&card_ids[1]);
+    // Step 8: Discard both cards
+    let faces: Vec<CardFace> = cards.iter().map(CardFace::from).collect();
+    for card in cards {
+        discard_card(ctx, card);
+    }
 
     // Step 9: Add one tank to the tile
-    ctx.db.tile().tile_id().update(Tile {
-        tanks: tile.tanks + 1,
-        ..tile
-    });
+    ctx.db.tile().tile_id().update(apply_build_tank(tile));
+
+    // Step 10: Update what each seat can see of the tile
+    refresh_sightings(ctx, player.game_id);
+    record_event(
+        ctx,
+        player.game_id,
+        Some(&player_color),
+        GameEventKind::TankBuilt(TileEvent { x, y }),
+    );
+    log_action(
+        ctx,
+        player.game_id,
+        &player_color,
+        GameActionKind::BuildTank(CardsAction { x, y, cards: faces }),
+    );
 
     Ok(())
 }
 
-/// Moves troops and tanks from one tile to an adjacent tile.
+/// Plays a poker hand from the player's cards on one of their tiles for a reward.
 ///
 /// # Arguments
 /// * `ctx` - The reducer context containing database access and sender information
-/// * `from_tile_id` - The ID of the source tile
-/// * `to_tile_id` - The ID of the destination tile
-/// * `troops_to_move` - Number of troops to move
-/// * `tanks_to_move` - Number of tanks to move
+/// * `x` - The x coordinate of the tile receiving the reward
+/// * `y` - The y coordinate of the tile receiving the reward
+/// * `card_ids` - The cards forming the hand, every one of which must count towards it
 ///
 /// # Returns
-/// * `Result<(), String>` - Ok(()) if move succeeds, Err with message if it fails
+/// * Nothing, the outcome is written to the caller's `action_result` row
 ///
-/// # Move Rules
-/// 1. Source and destination tiles must be owned by the player
-/// 2. Source and destination tiles must be adjacent
-/// 3. Source tile must have enough troops and tanks to move
-/// 4. Source tile must keep at least 1 troop after the move
+/// # Play Rules
+/// 1. It must be the player's turn (unless the game is simultaneous)
+/// 2. Target tile must be owned by the player
+/// 3. Player must own every card, and no card may be given twice
+/// 4. The cards must form a hand (see [`evaluate_hand`])
+/// 5. The cards are discarded, and the hand's reward is applied: tanks are added to
+///    the tile, gold to the player, and a defense buff to the tile for a number
+///    of turns (keeping any stronger or longer buff already on it)
 #[spacetimedb::reducer]
-pub fn move_units(
+pub fn play_cards(ctx: &ReducerContext, x: u32, y: u32, card_ids: Vec<u32>) {
+    let result = try_play_cards(ctx, x, y, card_ids);
+    record_action_result(ctx, "play_cards", result);
+}
+
+/// Validates and performs [`play_cards`], returning why it was rejected if it wasn't allowed.
+fn try_play_cards(
     ctx: &ReducerContext,
-    from_tile_id: u32,
-    to_tile_id: u32,
-    troops_to_move: u32,
-    tanks_to_move: u32,
-) -> Result<(), String> {
+    x: u32,
+    y: u32,
+    card_ids: Vec<u32>,
+) -> Result<(), GameError> {
     // Step 1: Get the current player
-    let player = ctx
-        .db
-        .player()
-        .iter()
-        .find(|p| p.identity == Some(ctx.sender))
-        .ok_or("Player not found")?;
+    let player = find_caller(ctx)?;
     let player_color = player.color.clone();
 
-    // Step 2: Get source and destination tiles
-    let from_tile = ctx
-        .db
-        .tile()
-        .tile_id()
-        .find(&from_tile_id)
-        .ok_or("Source tile not found")?;
-    let to_tile = ctx
-        .db
+    // Step 2: Verify it is the player's turn
+    let game = ensure_players_turn(ctx, &player)?;
+
+    // Step 3: Get the target tile in the player's game and verify ownership
+    let tile = find_tile(ctx, player.game_id, x, y).ok_or(GameError::TileNotFound)?;
+    if tile.owner_color.as_ref() != Some(&player_color) {
+        return Err(GameError::TileNotOwned);
+    }
+
+    // Step 4: Get the cards and verify ownership
+    let cards = find_owned_cards(ctx, &player, &card_ids)?;
+
+    // Step 5: Work out which hand the cards form
+    let hand: Vec<(&str, u8)> = cards
+        .iter()
+        .map(|card| (card.suit.as_str(), card.value))
+        .collect();
+    let rank = evaluate_hand(&hand).ok_or(GameError::InvalidHand)?;
+    let reward = rank.reward();
+
+    // Step 6: Spend the cards
+    let faces: Vec<CardFace> = cards.iter().map(CardFace::from).collect();
+    for card in cards {
+        discard_card(ctx, card);
+    }
+
+    // Step 7: Apply the reward to the tile
+    ctx.db
         .tile()
         .tile_id()
-        .find(&to_tile_id)
-        .ok_or("Destination tile not found")?;
+        .update(apply_hand_reward(tile, reward, game.turn));
 
-    // Step 3: Verify ownership of both tiles
-    if from_tile.owner_color.as_ref() != Some(&player_color)
-        || to_tile.owner_color.as_ref() != Some(&player_color)
-    {
-        return Err("You must own both the source and destination tiles".to_string());
-    }
+    // Step 8: Apply the reward to the player
+    let game_id = player.game_id;
+    ctx.db
+        .player()
+        .player_id()
+        .update(apply_hand_gold(player, reward));
+
+    // Step 9: Update what each seat can see of the tile
+    refresh_sightings(ctx, game_id);
+    record_event(
+        ctx,
+        game_id,
+        Some(&player_color),
+        GameEventKind::CardsPlayed(CardsPlayedEvent {
+            x,
+            y,
+            hand: rank,
+            tanks: reward.tanks,
+            gold: reward.gold,
+            defense_buff: reward.defense_buff,
+            buff_turns: reward.buff_turns,
+        }),
+    );
+    log_action(
+        ctx,
+        game_id,
+        &player_color,
+        GameActionKind::PlayCards(CardsAction { x, y, cards: faces }),
+    );
+
+    log::info!(
+        "PLAY CARDS SUCCESS:\n- Player: {}\n- Hand: {:?}\n- Tile: ({}, {})\n- Tanks added: {}\n- Gold added: {}\n- Defense buff: +{} for {} turns",
+        player_color,
+        rank,
+        x,
+        y,
+        reward.tanks,
+        reward.gold,
+        reward.defense_buff,
+        reward.buff_turns
+    );
+    Ok(())
+}
+
+/// Moves cards from the player's hand to the discard pile.
+///
+/// # Arguments
+/// * `ctx` - The reducer context containing database access and sender information
+/// * `card_ids` - The cards to discard
+///
+/// # Returns
+/// * Nothing, the outcome is written to the caller's `action_result` row
+///
+/// # Discard Rules
+/// 1. The game must still be in progress and the player must not have been eliminated
+/// 2. At least one card must be given, and no card may be given twice
+/// 3. Player must own every card
+/// 4. Cards can be discarded at any time, not only during the player's turn
+#[spacetimedb::reducer]
+pub fn discard_cards(ctx: &ReducerContext, card_ids: Vec<u32>) {
+    let result = try_discard_cards(ctx, card_ids);
+    record_action_result(ctx, "discard_cards", result);
+}
 
-    // Step 4: Verify tiles are adjacent
-    let dx = from_tile.x.abs_diff(to_tile.x);
-    let dy = from_tile.y.abs_diff(to_tile.y);
-    if dx + dy != 1 {
-        return Err("Tiles must be adjacent".to_string());
+/// Validates and performs [`discard_cards`], returning why it was rejected if it wasn't allowed.
+fn try_discard_cards(ctx: &ReducerContext, card_ids: Vec<u32>) -> Result<(), GameError> {
+    // Step 1: Verify at least one card is given
+    if card_ids.is_empty() {
+        return Err(GameError::NoCardsSelected);
     }
 
-    // Step 5: Verify enough units to move
-    if from_tile.troops < troops_to_move {
-        return Err("Not enough troops to move".to_string());
+    // Step 2: Get the current player and verify their game is running
+    let player = find_caller(ctx)?;
+    let game = ctx
+        .db
+        .game()
+        .game_id()
+        .find(player.game_id)
+        .ok_or(GameError::GameNotFound)?;
+    if game.status != GameStatus::InProgress {
+        return Err(GameError::GameOver);
     }
-    if from_tile.tanks < tanks_to_move {
-        return Err("Not enough tanks to move".to_string());
+    if player.eliminated {
+        return Err(GameError::PlayerEliminated);
     }
 
-    // Step 6: Verify source tile keeps at least 1 troop
-    if from_tile.troops - troops_to_move < 1 {
-        return Err("Source tile must keep at least 1 troop".to_string());
+    // Step 3: Get the cards and verify ownership
+    let cards = find_owned_cards(ctx, &player, &card_ids)?;
+
+    // Step 4: Move the cards to the discard pile
+    let faces: Vec<CardFace> = cards.iter().map(CardFace::from).collect();
+    for card in cards {
+        discard_card(ctx, card);
     }
 
-    // Step 7: Update source tile (remove units)
-    ctx.db.tile().tile_id().update(Tile {
-        troops: from_tile.troops - troops_to_move,
-        tanks: from_tile.tanks - tanks_to_move,
-        ..from_tile
-    });
+    log::info!("Player {} discarded {} cards", player.color, card_ids.len());
+    record_event(
+        ctx,
+        player.game_id,
+        Some(&player.color),
+        GameEventKind::CardsDiscarded(card_ids.len() as u32),
+    );
+    log_action(
+        ctx,
+        player.game_id,
+        &player.color,
+        GameActionKind::DiscardCards(faces),
+    );
+    Ok(())
+}
 
-    // Step 8: Update destination tile (add units)
-    ctx.db.tile().tile_id().update(Tile {
-        troops: to_tile.troops + troops_to_move,
-        tanks: to_tile.tanks + tanks_to_move,
-        ..to_tile
-    });
+/// Moves troops and tanks from one tile to an adjacent tile.
+///
+/// # Arguments
+/// * `ctx` - The reducer context containing database access and sender information
+/// * `from_x` - The x coordinate of the source tile
+/// * `from_y` - The y coordinate of the source tile
+/// * `to_x` - The x coordinate of the destination tile
+/// * `to_y` - The y coordinate of the destination tile
+/// * `troops_to_move` - Number of troops to move
+/// * `tanks_to_move` - Number of tanks to move
+///
+/// # Returns
+/// * Nothing, the outcome is written to the caller's `action_result` row
+///
+/// # Move Rules
+/// 1. It must be the player's turn (unless the game is simultaneous)
+/// 2. Source and destination tiles must exist on the player's board
+/// 3. Destination tile must be passable
+/// 4. Source and destination tiles must be owned by the player
+/// 5. Source and destination tiles must be adjacent
+/// 6. Source tile must have enough troops and tanks to move
+/// 7. Source tile must keep at least 1 troop after the move
+/// 8. Player must be within the game's hand size limit and have at least the game's move stamina cost, which is spent on success
+#[spacetimedb::reducer]
+pub fn move_units(
+    ctx: &ReducerContext,
+    from_x: u32,
+    from_y: u32,
+    to_x: u32,
+    to_y: u32,
+    troops_to_move: u32,
+    tanks_to_move: u32,
+) {
+    let result = try_move_units(
+        ctx,
+        from_x,
+        from_y,
+        to_x,
+        to_y,
+        troops_to_move,
+        tanks_to_move,
+    );
+    record_action_result(ctx, "move_units", result);
+}
+
+/// Validates and performs [`move_units`], returning why it was rejected if it wasn't allowed.
+fn try_move_units(
+    ctx: &ReducerContext,
+    from_x: u32,
+    from_y: u32,
+    to_x: u32,
+    to_y: u32,
+    troops_to_move: u32,
+    tanks_to_move: u32,
+) -> Result<(), GameError> {
+    // Step 1: Get the current player
+    let player = find_caller(ctx)?;
+    let player_color = player.color.clone();
+
+    // Step 2: Verify it is the player's turn
+    ensure_players_turn(ctx, &player)?;
+
+    // Step 3: Get source and destination tiles in the player's game
+    let from_tile =
+        find_tile(ctx, player.game_id, from_x, from_y).ok_or(GameError::SourceTileNotFound)?;
+    let to_tile =
+        find_tile(ctx, player.game_id, to_x, to_y).ok_or(GameError::DestinationTileNotFound)?;
+
+    // Step 4: Verify the move follows the rules
+    let config = ctx
+        .db
+        .game_config()
+        .game_id()
+        .find(player.game_id)
+        .ok_or(GameError::GameConfigNotFound)?;
+    check_move(
+        &config,
+        &player,
+        &from_tile,
+        &to_tile,
+        troops_to_move,
+        tanks_to_move,
+        hand_size(ctx, player.game_id, &player_color),
+    )?;
+
+    // Step 5: Move the units and spend the moving player's stamina
+    let game_id = player.game_id;
+    let (player, from_tile, to_tile) = apply_move(
+        &config,
+        player,
+        from_tile,
+        to_tile,
+        troops_to_move,
+        tanks_to_move,
+    );
+    ctx.db.tile().tile_id().update(from_tile);
+    ctx.db.tile().tile_id().update(to_tile);
+    ctx.db.player().player_id().update(player);
+
+    // Step 8: Update what each seat can see of both tiles
+    refresh_sightings(ctx, game_id);
+    record_event(
+        ctx,
+        game_id,
+        Some(&player_color),
+        GameEventKind::UnitsMoved(MoveEvent {
+            from_x,
+            from_y,
+            to_x,
+            to_y,
+            troops: troops_to_move,
+            tanks: tanks_to_move,
+        }),
+    );
+    log_action(
+        ctx,
+        game_id,
+        &player_color,
+        GameActionKind::MoveUnits(UnitsAction {
+            from_x,
+            from_y,
+            to_x,
+            to_y,
+            troops: troops_to_move,
+            tanks: tanks_to_move,
+        }),
+    );
 
     log::info!(
-        "Player {} moved {} troops and {} tanks from tile {} to tile {}",
+        "Player {} moved {} troops and {} tanks from ({}, {}) to ({}, {})",
         player_color,
         troops_to_move,
         tanks_to_move,
-        from_tile_id,
-        to_tile_id
+        from_x,
+        from_y,
+        to_x,
+        to_y
     );
 
     Ok(())
 }
+
+/// Replays a game from its seed and action log and checks the result matches the game.
+///
+/// This only reads the game, so anyone can verify any game, including finished ones.
+/// While a game is in progress a mismatch only names the part that differs, since
+/// its values could show units hidden by fog of war or cards in a player's hand.
+///
+/// # Arguments
+/// * `ctx` - The reducer context containing database access and sender information
+/// * `game_id` - The ID of the game to verify
+///
+/// # Returns
+/// * Nothing, the outcome is written to the caller's `action_result` row, with
+///   the first difference found if the replay doesn't match
+#[reducer]
+pub fn verify_game(ctx: &ReducerContext, game_id: u64) {
+    let result = try_verify_game(ctx, game_id);
+    record_action_result(ctx, "verify_game", result);
+}
+
+/// Performs [`verify_game`], returning the first difference if the replay didn't match.
+fn try_verify_game(ctx: &ReducerContext, game_id: u64) -> Result<(), GameError> {
+    // Step 1: Get the game and its rules
+    let game = ctx
+        .db
+        .game()
+        .game_id()
+        .find(game_id)
+        .ok_or(GameError::GameNotFound)?;
+    let config = ctx
+        .db
+        .game_config()
+        .game_id()
+        .find(game_id)
+        .ok_or(GameError::GameConfigNotFound)?;
+
+    // Step 2: Replay the game's actions in the order they were accepted
+    let mut actions: Vec<GameAction> = ctx.db.game_action().game_id().filter(game_id).collect();
+    actions.sort_by_key(|action| action.action_id);
+    let replayed = replay(game_id, &config, game.rng_seed, &actions);
+
+    // Step 3: Compare the replayed state with the game's
+    let tiles: Vec<Tile> = ctx.db.tile().game_id().filter(game_id).collect();
+    let players: Vec<Player> = ctx.db.player().game_id().filter(game_id).collect();
+    let cards = game_cards(ctx, game_id);
+    if let Some(difference) = replayed.find_difference(&game, &tiles, &players, &cards) {
+        log::warn!(
+            "Game {} doesn't match its replay: {}",
+            game_id,
+            difference.details
+        );
+        let difference = if game.status == GameStatus::InProgress {
+            format!("a {} doesn't match", difference.kind.name())
+        } else {
+            difference.details
+        };
+        return Err(GameError::ReplayMismatch { difference });
+    }
+
+    log::info!(
+        "Game {} matches its replay of {} actions",
+        game_id,
+        actions.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,560 @@
+use crate::cards::evaluate_hand;
+use crate::rules::{
+    apply_build_infantry, apply_build_tank, apply_hand_gold, apply_hand_reward, apply_move,
+    award_turn_start, calculate_income, deal_cards, discard_over_hand_limit, evaluate_victory,
+    new_board, new_deck, new_seats, next_turn_number, resolve_attack, spend_stamina, turn_rng,
+};
+use crate::{
+    seat_color, Card, Game, GameAction, GameConfig, GameStatus, Player, Tile, DEAL_STREAM,
+    HAND_LIMIT_STREAM,
+};
+use spacetimedb::Identity;
+
+// ------------------------------------------------------------
+// Replay
+// ------------------------------------------------------------
+
+/// A card named by its suit and value, which stay the same when a game is
+/// replayed while its `card_id` doesn't.
+#[derive(spacetimedb::SpacetimeType, Clone, Debug, PartialEq, Eq)]
+pub struct CardFace {
+    pub suit: String,
+    pub value: u8,
+}
+
+impl From<&Card> for CardFace {
+    fn from(card: &Card) -> Self {
+        CardFace {
+            suit: card.suit.clone(),
+            value: card.value,
+        }
+    }
+}
+
+#[derive(spacetimedb::SpacetimeType, Clone, Debug)]
+pub struct TileAction {
+    pub x: u32,
+    pub y: u32,
+}
+
+#[derive(spacetimedb::SpacetimeType, Clone, Debug)]
+pub struct UnitsAction {
+    pub from_x: u32,
+    pub from_y: u32,
+    pub to_x: u32,
+    pub to_y: u32,
+    pub troops: u32,
+    pub tanks: u32,
+}
+
+#[derive(spacetimedb::SpacetimeType, Clone, Debug)]
+pub struct CardsAction {
+    pub x: u32,
+    pub y: u32,
+    pub cards: Vec<CardFace>,
+}
+
+/// An accepted reducer call that changed a game's state, as recorded in its action log.
+#[derive(spacetimedb::SpacetimeType, Clone, Debug)]
+pub enum GameActionKind {
+    JoinGame(Identity), // The identity that claimed the seat
+    LeaveGame,
+    AdvanceTurn(u32), // The seat whose turn started, whether the timer or `end_turn` passed it
+    BuildInfantry(TileAction),
+    Attack(UnitsAction),
+    BuildTank(CardsAction),
+    PlayCards(CardsAction),
+    DiscardCards(Vec<CardFace>),
+    MoveUnits(UnitsAction),
+}
+
+/// Which part of a game didn't match its replay.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DifferenceKind {
+    Turn,
+    Status,
+    Tile,
+    Player,
+    Card,
+}
+
+impl DifferenceKind {
+    /// Returns the name of the part that differs, which is safe to show while
+    /// the game is in progress since it gives away no hidden units or cards.
+    pub fn name(self) -> &'static str {
+        match self {
+            DifferenceKind::Turn => "turn",
+            DifferenceKind::Status => "status",
+            DifferenceKind::Tile => "tile",
+            DifferenceKind::Player => "player",
+            DifferenceKind::Card => "card",
+        }
+    }
+}
+
+/// The first difference found between a game and its replay.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayDifference {
+    pub kind: DifferenceKind,
+    pub details: String, // Shows the replayed and live values, including hidden units and cards
+}
+
+/// A game's state as rebuilt by [`replay`].
+///
+/// Player and card IDs are handed out by the database rather than the game, so
+/// replayed ones are 0, and connection state such as `online` isn't replayed.
+pub struct ReplayState {
+    pub turn: u64,
+    pub current_player_index: u32,
+    pub status: GameStatus,
+    pub winner: Option<String>,
+    pub tiles: Vec<Tile>,
+    pub players: Vec<Player>,
+    pub cards: Vec<Card>, // In the order the deck was created, like live cards in `card_id` order
+}
+
+/// Rebuilds a game's tiles, players and cards from its seed and the actions it accepted.
+///
+/// Every random draw in a game comes from its seed, so replaying the same actions
+/// always gives the same state. Actions are applied as they were accepted, without
+/// validating them again.
+///
+/// # Arguments
+/// * `game_id` - The game being replayed, which its tile IDs are derived from
+/// * `config` - The game's rules
+/// * `seed` - The game's `rng_seed`
+/// * `actions` - The game's action log, in `action_id` order
+///
+/// # Returns
+/// * `ReplayState` - The game's state after the last action
+pub fn replay(game_id: u64, config: &GameConfig, seed: u64, actions: &[GameAction]) -> ReplayState {
+    let mut state = ReplayState {
+        turn: 1,
+        current_player_index: 0,
+        status: GameStatus::InProgress,
+        winner: None,
+        tiles: new_board(game_id, config, seed),
+        players: new_seats(game_id, config),
+        cards: new_deck(game_id),
+    };
+    state.start_turn(config, seed, 0);
+    for action in actions {
+        state.apply(config, seed, &action.color, &action.kind);
+    }
+    state
+}
+
+impl ReplayState {
+    fn tile_mut(&mut self, x: u32, y: u32) -> Option<&mut Tile> {
+        self.tiles.iter_mut().find(|t| t.x == x && t.y == y)
+    }
+
+    fn player_mut(&mut self, color: &str) -> Option<&mut Player> {
+        self.players.iter_mut().find(|p| p.color == color)
+    }
+
+    /// Replaces a tile with its updated state.
+    fn set_tile(&mut self, tile: Tile) {
+        if let Some(slot) = self.tile_mut(tile.x, tile.y) {
+            *slot = tile;
+        }
+    }
+
+    /// Replaces a player with their updated state.
+    fn set_player(&mut self, player: Player) {
+        if let Some(slot) = self.player_mut(&player.color) {
+            *slot = player;
+        }
+    }
+
+    /// Moves the given cards to the discard pile.
+    fn discard(&mut self, faces: &[CardFace]) {
+        for card in self
+            .cards
+            .iter_mut()
+            .filter(|card| faces.contains(&CardFace::from(&**card)))
+        {
+            card.owner_color = None;
+            card.discarded = true;
+        }
+    }
+
+    /// Applies one logged action the same way the reducer that accepted it did.
+    fn apply(&mut self, config: &GameConfig, seed: u64, color: &str, kind: &GameActionKind) {
+        match kind {
+            GameActionKind::JoinGame(identity) => {
+                if let Some(player) = self.player_mut(color) {
+                    player.identity = Some(*identity);
+                }
+            }
+            GameActionKind::LeaveGame => {
+                if let Some(player) = self.player_mut(color) {
+                    player.identity = None;
+                }
+            }
+            GameActionKind::AdvanceTurn(seat) => self.advance_turn(config, seed, *seat),
+            GameActionKind::BuildInfantry(TileAction { x, y }) => {
+                let player = self.player_mut(color).cloned();
+                let tile = self.tile_mut(*x, *y).cloned();
+                let (Some(player), Some(tile)) = (player, tile) else {
+                    return;
+                };
+                let (player, tile) = apply_build_infantry(config, player, tile);
+                self.set_player(player);
+                self.set_tile(tile);
+            }
+            GameActionKind::Attack(action) => self.attack(config, color, action),
+            GameActionKind::BuildTank(CardsAction { x, y, cards }) => {
+                self.discard(cards);
+                if let Some(tile) = self.tile_mut(*x, *y) {
+                    *tile = apply_build_tank(tile.clone());
+                }
+            }
+            GameActionKind::PlayCards(CardsAction { x, y, cards }) => {
+                let hand: Vec<(&str, u8)> = cards
+                    .iter()
+                    .map(|card| (card.suit.as_str(), card.value))
+                    .collect();
+                let Some(reward) = evaluate_hand(&hand).map(|rank| rank.reward()) else {
+                    return;
+                };
+                self.discard(cards);
+                let turn = self.turn;
+                if let Some(tile) = self.tile_mut(*x, *y) {
+                    *tile = apply_hand_reward(tile.clone(), reward, turn);
+                }
+                if let Some(player) = self.player_mut(color) {
+                    *player = apply_hand_gold(player.clone(), reward);
+                }
+            }
+            GameActionKind::DiscardCards(cards) => self.discard(cards),
+            GameActionKind::MoveUnits(action) => {
+                let player = self.player_mut(color).cloned();
+                let from = self.tile_mut(action.from_x, action.from_y).cloned();
+                let to = self.tile_mut(action.to_x, action.to_y).cloned();
+                let (Some(player), Some(from), Some(to)) = (player, from, to) else {
+                    return;
+                };
+                let (player, from, to) =
+                    apply_move(config, player, from, to, action.troops, action.tanks);
+                self.set_player(player);
+                self.set_tile(from);
+                self.set_tile(to);
+            }
+        }
+    }
+
+    /// Resolves an attack and checks whether a capture ended the game.
+    fn attack(&mut self, config: &GameConfig, color: &str, action: &UnitsAction) {
+        let from = self.tile_mut(action.from_x, action.from_y).cloned();
+        let to = self.tile_mut(action.to_x, action.to_y).cloned();
+        let (Some(from), Some(to)) = (from, to) else {
+            return;
+        };
+        let outcome = resolve_attack(config, &from, &to, action.troops, action.tanks, self.turn);
+        self.set_tile(outcome.from_tile);
+        self.set_tile(outcome.to_tile);
+        if let Some(player) = self.player_mut(color) {
+            *player = spend_stamina(player.clone(), config.attack_stamina_cost);
+        }
+        if outcome.captured {
+            self.check_victory(config);
+        }
+    }
+
+    /// Passes the turn to a seat, ending the game if the turn limit has been passed.
+    fn advance_turn(&mut self, config: &GameConfig, seed: u64, seat: u32) {
+        let outgoing = self.current_player_index;
+        let mut rng = turn_rng(seed, self.turn, outgoing, HAND_LIMIT_STREAM);
+        discard_over_hand_limit(
+            &mut self.cards,
+            seat_color(outgoing),
+            config.max_hand_size,
+            &mut rng,
+        );
+
+        self.turn = next_turn_number(self.turn, outgoing, seat);
+        self.current_player_index = seat;
+        if self.check_victory(config) {
+            return;
+        }
+        self.start_turn(config, seed, seat);
+    }
+
+    /// Awards a claimed seat its income and stamina and deals its cards.
+    fn start_turn(&mut self, config: &GameConfig, seed: u64, seat: u32) {
+        let color = seat_color(seat);
+        let Some(income) = self
+            .players
+            .iter()
+            .find(|p| p.color == color && p.identity.is_some())
+            .map(|player| calculate_income(config, &self.tiles, player).total)
+        else {
+            return;
+        };
+        if let Some(player) = self.player_mut(color) {
+            *player = award_turn_start(config, player.clone(), income);
+        }
+
+        let mut rng = turn_rng(seed, self.turn, seat, DEAL_STREAM);
+        deal_cards(&mut self.cards, color, config.cards_per_turn, &mut rng);
+    }
+
+    /// Marks eliminated players and finishes the game if it is over.
+    ///
+    /// # Returns
+    /// * `bool` - true if the game is over
+    fn check_victory(&mut self, config: &GameConfig) -> bool {
+        if self.status != GameStatus::InProgress {
+            return true;
+        }
+        let outcome = evaluate_victory(config, self.turn, &self.tiles, &self.players);
+        for player in self
+            .players
+            .iter_mut()
+            .filter(|p| outcome.eliminated.contains(&p.color))
+        {
+            player.eliminated = true;
+        }
+        if outcome.finished {
+            self.status = GameStatus::Finished;
+            self.winner = outcome.winner;
+        }
+        outcome.finished
+    }
+
+    /// Compares the replayed state with a game's live state.
+    ///
+    /// # Returns
+    /// * `Option<ReplayDifference>` - The first difference found, or None if they match
+    pub fn find_difference(
+        &self,
+        game: &Game,
+        tiles: &[Tile],
+        players: &[Player],
+        cards: &[Card],
+    ) -> Option<ReplayDifference> {
+        if (self.turn, self.current_player_index) != (game.turn, game.current_player_index) {
+            return Some(ReplayDifference {
+                kind: DifferenceKind::Turn,
+                details: format!(
+                    "turn {} of seat {} was replayed, but the game is on turn {} of seat {}",
+                    self.turn, self.current_player_index, game.turn, game.current_player_index
+                ),
+            });
+        }
+        if (self.status, &self.winner) != (game.status, &game.winner) {
+            return Some(ReplayDifference {
+                kind: DifferenceKind::Status,
+                details: format!(
+                    "the replayed game ended as {:?} won by {:?}, but the game is {:?} won by {:?}",
+                    self.status, self.winner, game.status, game.winner
+                ),
+            });
+        }
+
+        for replayed in &self.tiles {
+            let Some(live) = tiles.iter().find(|t| t.tile_id == replayed.tile_id) else {
+                return Some(ReplayDifference {
+                    kind: DifferenceKind::Tile,
+                    details: format!("tile ({}, {}) is missing", replayed.x, replayed.y),
+                });
+            };
+            let state = |t: &Tile| {
+                (
+                    t.terrain,
+                    t.owner_color.clone(),
+                    t.troops,
+                    t.tanks,
+                    t.defense_buff,
+                    t.buff_until_turn,
+                )
+            };
+            if state(replayed) != state(live) {
+                return Some(ReplayDifference {
+                    kind: DifferenceKind::Tile,
+                    details: format!(
+                        "tile ({}, {}) was replayed as {:?}, but is {:?}",
+                        replayed.x,
+                        replayed.y,
+                        state(replayed),
+                        state(live)
+                    ),
+                });
+            }
+        }
+
+        for replayed in &self.players {
+            let Some(live) = players.iter().find(|p| p.color == replayed.color) else {
+                return Some(ReplayDifference {
+                    kind: DifferenceKind::Player,
+                    details: format!("player {} is missing", replayed.color),
+                });
+            };
+            let state = |p: &Player| (p.identity, p.gold, p.stamina, p.eliminated);
+            if state(replayed) != state(live) {
+                return Some(ReplayDifference {
+                    kind: DifferenceKind::Player,
+                    details: format!(
+                        "player {} was replayed as {:?}, but is {:?}",
+                        replayed.color,
+                        state(replayed),
+                        state(live)
+                    ),
+                });
+            }
+        }
+
+        for replayed in &self.cards {
+            let Some(live) = cards
+                .iter()
+                .find(|c| c.suit == replayed.suit && c.value == replayed.value)
+            else {
+                return Some(ReplayDifference {
+                    kind: DifferenceKind::Card,
+                    details: format!("card {} of {} is missing", replayed.value, replayed.suit),
+                });
+            };
+            let state = |c: &Card| (c.owner_color.clone(), c.discarded);
+            if state(replayed) != state(live) {
+                return Some(ReplayDifference {
+                    kind: DifferenceKind::Card,
+                    details: format!(
+                        "card {} of {} was replayed as {:?}, but is {:?}",
+                        replayed.value,
+                        replayed.suit,
+                        state(replayed),
+                        state(live)
+                    ),
+                });
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::default_game_config;
+    use spacetimedb::Timestamp;
+
+    const SEED: u64 = 42;
+
+    fn action(color: &str, kind: GameActionKind) -> GameAction {
+        GameAction {
+            action_id: 0,
+            game_id: 0,
+            color: color.to_string(),
+            kind,
+            timestamp: Timestamp::UNIX_EPOCH,
+        }
+    }
+
+    fn join_and_play_turns(turns: usize) -> Vec<GameAction> {
+        let mut actions = vec![action("red", GameActionKind::JoinGame(Identity::ZERO))];
+        actions.extend((0..turns).map(|_| action("red", GameActionKind::AdvanceTurn(0))));
+        actions
+    }
+
+    fn hand(state: &ReplayState, color: &str) -> Vec<CardFace> {
+        state
+            .cards
+            .iter()
+            .filter(|c| c.owner_color.as_deref() == Some(color))
+            .map(CardFace::from)
+            .collect()
+    }
+
+    fn red(state: &ReplayState) -> &Player {
+        &state.players[0]
+    }
+
+    #[test]
+    fn claimed_seats_get_income_stamina_and_cards_when_their_turn_starts() {
+        let state = replay(0, &default_game_config(), SEED, &join_and_play_turns(1));
+        assert_eq!((state.turn, state.current_player_index), (2, 0));
+        assert_eq!((red(&state).gold, red(&state).stamina), (2, 1));
+        assert_eq!(hand(&state, "red").len(), 2);
+        assert!(state.players[1..].iter().all(|p| p.gold == 0));
+    }
+
+    #[test]
+    fn the_same_seed_and_actions_give_the_same_game() {
+        let config = default_game_config();
+        let first = replay(0, &config, SEED, &join_and_play_turns(3));
+        let second = replay(0, &config, SEED, &join_and_play_turns(3));
+        assert_eq!(hand(&first, "red"), hand(&second, "red"));
+        let other = replay(0, &config, SEED + 1, &join_and_play_turns(3));
+        assert_ne!(hand(&first, "red"), hand(&other, "red"));
+    }
+
+    #[test]
+    fn cards_over_the_hand_limit_are_discarded_when_the_turn_ends() {
+        let config = GameConfig {
+            cards_per_turn: 5,
+            ..default_game_config()
+        };
+        let state = replay(0, &config, SEED, &join_and_play_turns(2));
+        assert_eq!(hand(&state, "red").len(), 10);
+
+        let state = replay(0, &config, SEED, &join_and_play_turns(3));
+        assert_eq!(hand(&state, "red").len(), 12);
+        assert_eq!(state.cards.iter().filter(|c| c.discarded).count(), 3);
+    }
+
+    #[test]
+    fn stamina_is_capped() {
+        let state = replay(0, &default_game_config(), SEED, &join_and_play_turns(4));
+        assert_eq!(red(&state).stamina, 2);
+    }
+
+    #[test]
+    fn attacks_are_replayed() {
+        let mut actions = join_and_play_turns(1);
+        actions.push(action(
+            "red",
+            GameActionKind::Attack(UnitsAction {
+                from_x: 1,
+                from_y: 1,
+                to_x: 1,
+                to_y: 2,
+                troops: 4,
+                tanks: 0,
+            }),
+        ));
+        let state = replay(0, &default_game_config(), SEED, &actions);
+        let tile = |x, y| state.tiles.iter().find(|t| (t.x, t.y) == (x, y)).unwrap();
+        assert_eq!(tile(1, 1).troops, 1);
+        assert_eq!(tile(1, 2).owner_color.as_deref(), Some("red"));
+        assert_eq!(red(&state).stamina, 0);
+    }
+
+    #[test]
+    fn a_replayed_game_matches_itself_and_reports_differences() {
+        let config = default_game_config();
+        let state = replay(0, &config, SEED, &join_and_play_turns(1));
+        let game = Game {
+            game_id: 0,
+            game_name: "test".to_string(),
+            turn: state.turn,
+            current_player_index: state.current_player_index,
+            simultaneous: false,
+            status: state.status,
+            winner: state.winner.clone(),
+            turn_deadline: Timestamp::UNIX_EPOCH,
+            rng_seed: SEED,
+        };
+        let mut players = state.players.clone();
+        assert_eq!(
+            state.find_difference(&game, &state.tiles, &players, &state.cards),
+            None
+        );
+
+        players[0].gold += 1;
+        assert!(state
+            .find_difference(&game, &state.tiles, &players, &state.cards)
+            .is_some_and(|difference| difference.kind == DifferenceKind::Player
+                && difference.details.starts_with("player red")));
+    }
+}
@@ -0,0 +1,1882 @@
+use crate::cards::HandReward;
+use crate::error::GameError;
+use crate::terrain::{generate_terrain, Terrain};
+use crate::{
+    seat_color, tile_id, Card, Game, GameConfig, GameStatus, Player, PlayerIncome, Tile,
+    MAX_BOARD_SIZE, MAX_GOLD_MINE_INCOME, MAX_GOLD_PER_TURN, MAX_NATURAL_DEFENSE,
+    MAX_OFFLINE_GRACE_SECS, MAX_REGION_BONUS_GOLD, MAX_STAMINA_CAP, MAX_STAMINA_PER_TURN,
+    MAX_STARTING_TROOPS, MAX_TANK_POWER, MAX_TURN_DURATION_SECS, MAX_TURN_LIMIT,
+    MAX_VICTORY_TERRITORY_PERCENT, MIN_BOARD_SIZE, MIN_PLAYERS, PLAYER_PALETTE,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use spacetimedb::Timestamp;
+use std::collections::HashSet;
+
+// The game's rules as pure functions over in-memory tiles, players and cards.
+// Reducers load what a rule needs from the database, call it, and write back
+// the result, so every rule can be tested and replayed without a database.
+
+// ------------------------------------------------------------
+// Setup
+// ------------------------------------------------------------
+
+/// Verifies a game's rules can be played before the game is created.
+///
+/// # Config Rules
+/// 1. The board's width and height must both be between `MIN_BOARD_SIZE` and `MAX_BOARD_SIZE`
+/// 2. There must be between `MIN_PLAYERS` and one player per palette color
+/// 3. Tanks must be worth between 1 and `MAX_TANK_POWER`
+/// 4. Turns must last between 1 second and `MAX_TURN_DURATION_SECS`
+/// 5. `victory_territory_percent`, `turn_limit` and `offline_grace_secs` must be at most their `MAX_*` limits
+/// 6. Unit, income and stamina amounts must be at most their `MAX_*` limits
+pub fn validate_game_config(config: &GameConfig) -> Result<(), GameError> {
+    let board_sizes = MIN_BOARD_SIZE..=MAX_BOARD_SIZE;
+    if !board_sizes.contains(&config.board_width) || !board_sizes.contains(&config.board_height) {
+        return Err(GameError::InvalidGameConfig {
+            reason: format!(
+                "board_width and board_height must be between {} and {}",
+                MIN_BOARD_SIZE, MAX_BOARD_SIZE
+            ),
+        });
+    }
+
+    let max_players = PLAYER_PALETTE.len() as u32;
+    if !(MIN_PLAYERS..=max_players).contains(&config.player_count) {
+        return Err(GameError::InvalidGameConfig {
+            reason: format!(
+                "player_count must be between {} and {}",
+                MIN_PLAYERS, max_players
+            ),
+        });
+    }
+
+    if !(1..=MAX_TANK_POWER).contains(&config.tank_power) {
+        return Err(GameError::InvalidGameConfig {
+            reason: format!("tank_power must be between 1 and {}", MAX_TANK_POWER),
+        });
+    }
+    if !(1..=MAX_TURN_DURATION_SECS).contains(&config.turn_duration_secs) {
+        return Err(GameError::InvalidGameConfig {
+            reason: format!(
+                "turn_duration_secs must be between 1 and {}",
+                MAX_TURN_DURATION_SECS
+            ),
+        });
+    }
+
+    if config.victory_territory_percent > MAX_VICTORY_TERRITORY_PERCENT {
+        return Err(GameError::InvalidGameConfig {
+            reason: format!(
+                "victory_territory_percent must be at most {}",
+                MAX_VICTORY_TERRITORY_PERCENT
+            ),
+        });
+    }
+    if config.turn_limit > MAX_TURN_LIMIT {
+        return Err(GameError::InvalidGameConfig {
+            reason: format!("turn_limit must be at most {}", MAX_TURN_LIMIT),
+        });
+    }
+    if config.offline_grace_secs > MAX_OFFLINE_GRACE_SECS {
+        return Err(GameError::InvalidGameConfig {
+            reason: format!(
+                "offline_grace_secs must be at most {}",
+                MAX_OFFLINE_GRACE_SECS
+            ),
+        });
+    }
+
+    let amount_limits = [
+        (
+            "natural_defense",
+            config.natural_defense,
+            MAX_NATURAL_DEFENSE,
+        ),
+        (
+            "starting_troops",
+            config.starting_troops,
+            MAX_STARTING_TROOPS,
+        ),
+        ("gold_per_turn", config.gold_per_turn, MAX_GOLD_PER_TURN),
+        (
+            "gold_mine_income",
+            config.gold_mine_income,
+            MAX_GOLD_MINE_INCOME,
+        ),
+        (
+            "region_bonus_gold",
+            config.region_bonus_gold,
+            MAX_REGION_BONUS_GOLD,
+        ),
+        (
+            "stamina_per_turn",
+            config.stamina_per_turn,
+            MAX_STAMINA_PER_TURN,
+        ),
+        ("max_stamina", config.max_stamina, MAX_STAMINA_CAP),
+    ];
+    for (field, value, max) in amount_limits {
+        if value > max {
+            return Err(GameError::InvalidGameConfig {
+                reason: format!("{} must be at most {}", field, max),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Builds a new game's unclaimed seats, one for each configured player.
+pub fn new_seats(game_id: u64, config: &GameConfig) -> Vec<Player> {
+    (0..config.player_count)
+        .map(|seat| Player {
+            player_id: 0,
+            game_id,
+            color: seat_color(seat).to_string(),
+            identity: None,
+            online: false,
+            gold: 0,    // Initialize gold to 0
+            stamina: 0, // Initialize stamina to 0
+            eliminated: false,
+            last_seen: None,
+        })
+        .collect()
+}
+
+/// Builds a new game's tiles on the map generated from its seed, giving each
+/// seat its base tile with the starting troops.
+pub fn new_board(game_id: u64, config: &GameConfig, seed: u64) -> Vec<Tile> {
+    let board = config.board();
+    let starts = board.start_positions(config.player_count);
+    let map = generate_terrain(seed, &board, &starts);
+    board
+        .tiles()
+        .map(|(x, y)| {
+            // Initialize all tiles with natural defense
+            let mut tile = Tile {
+                tile_id: tile_id(game_id, x, y),
+                game_id,
+                x,
+                y,
+                terrain: map[x as usize][y as usize],
+                owner_color: None,
+                troops: 0,
+                tanks: 0,
+                defense_buff: 0,
+                buff_until_turn: 0,
+            };
+            if let Some(seat) = starts.iter().position(|&start| start == (x, y)) {
+                tile.owner_color = Some(seat_color(seat as u32).to_string());
+                tile.troops = config.starting_troops;
+            }
+            tile
+        })
+        .collect()
+}
+
+/// Builds a new game's deck, in the order its cards are stored.
+pub fn new_deck(game_id: u64) -> Vec<Card> {
+    let suits = ["hearts", "diamonds", "clubs", "spades"];
+    let mut deck = Vec::with_capacity(suits.len() * 13);
+    for suit in suits.iter() {
+        for value in 1..=13 {
+            deck.push(Card {
+                card_id: 0,
+                game_id,
+                suit: suit.to_string(),
+                value,
+                owner_color: None, // Cards start in the deck
+                discarded: false,
+            });
+        }
+    }
+    deck
+}
+
+/// Derives the ID of a tile on a game's board from player-given coordinates.
+///
+/// # Returns
+/// * `Option<u64>` - The tile's ID, or None if the coordinates are off the board
+pub fn board_tile_id(config: &GameConfig, game_id: u64, x: u32, y: u32) -> Option<u64> {
+    config
+        .board()
+        .contains(x, y)
+        .then(|| tile_id(game_id, x, y))
+}
+
+// ------------------------------------------------------------
+// Turns
+// ------------------------------------------------------------
+
+/// Verifies a player may act in their game right now.
+///
+/// # Arguments
+/// * `game` - The player's game
+/// * `player` - The acting player
+/// * `simultaneous` - Whether any player may act instead of only the current one
+///
+/// # Turn Rules
+/// 1. The game must still be in progress
+/// 2. The player must not have been eliminated
+/// 3. It must be the player's turn, unless `simultaneous` is set
+pub fn check_turn(game: &Game, player: &Player, simultaneous: bool) -> Result<(), GameError> {
+    if game.status != GameStatus::InProgress {
+        return Err(GameError::GameOver);
+    }
+    if player.eliminated {
+        return Err(GameError::PlayerEliminated);
+    }
+    if simultaneous {
+        return Ok(());
+    }
+
+    let current_color = seat_color(game.current_player_index);
+    if player.color != current_color {
+        return Err(GameError::NotYourTurn {
+            current_color: current_color.to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Checks whether a seat should get a turn in the rotation.
+///
+/// Unclaimed seats and eliminated players never do. Offline players keep their
+/// turns for a grace period after they were last seen so they can reconnect,
+/// after which their turns are passed automatically.
+pub fn can_take_turn(player: &Player, now: Timestamp, offline_grace_secs: u64) -> bool {
+    if player.identity.is_none() || player.eliminated {
+        return false;
+    }
+    if player.online {
+        return true;
+    }
+    player.last_seen.is_some_and(|last_seen| {
+        now.duration_since(last_seen)
+            .is_none_or(|offline| offline.as_secs() <= offline_grace_secs)
+    })
+}
+
+/// Finds the seat whose turn comes after the current one, going round the table
+/// in seat order and skipping seats that can't take a turn (see [`can_take_turn`]).
+///
+/// # Returns
+/// * `Option<u32>` - The next seat, or None if no seat can take a turn, not even the current one
+pub fn next_seat(
+    config: &GameConfig,
+    current_index: u32,
+    players: &[Player],
+    now: Timestamp,
+) -> Option<u32> {
+    let seats = config.player_count;
+    (1..=seats)
+        .map(|offset| (current_index + offset) % seats)
+        .find(|&index| {
+            players.iter().any(|p| {
+                p.color == seat_color(index) && can_take_turn(p, now, config.offline_grace_secs)
+            })
+        })
+}
+
+/// Checks a seat can pass its turn early, which it can only do to another seat.
+///
+/// Otherwise passing would hand the turn straight back, starting a new turn with
+/// its income and cards every time.
+pub fn check_pass_turn(
+    config: &GameConfig,
+    current_index: u32,
+    players: &[Player],
+    now: Timestamp,
+) -> Result<(), GameError> {
+    match next_seat(config, current_index, players, now) {
+        Some(index) if index != current_index => Ok(()),
+        _ => Err(GameError::NoOtherPlayers),
+    }
+}
+
+/// Returns the turn number once the turn passes from one seat to another, which
+/// goes up every time the rotation goes round the table.
+pub fn next_turn_number(turn: u64, outgoing: u32, incoming: u32) -> u64 {
+    if incoming <= outgoing {
+        turn + 1
+    } else {
+        turn
+    }
+}
+
+/// Creates the random number generator for one kind of draw on a seat's turn.
+///
+/// Every draw is derived from the game's seed rather than the reducer's RNG, so
+/// replaying a game's actions from its seed draws exactly the same cards.
+pub fn turn_rng(seed: u64, turn: u64, seat: u32, stream: u64) -> StdRng {
+    let draw = turn.wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (((seat as u64) << 8) | stream).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    StdRng::seed_from_u64(seed ^ draw)
+}
+
+/// Awards a seat its income and stamina at the start of its turn, with stamina capped at `max_stamina`.
+pub fn award_turn_start(config: &GameConfig, player: Player, income: u32) -> Player {
+    Player {
+        gold: player.gold.saturating_add(income),
+        stamina: (player.stamina + config.stamina_per_turn).min(config.max_stamina),
+        ..player
+    }
+}
+
+// ------------------------------------------------------------
+// Cards
+// ------------------------------------------------------------
+
+/// Deals cards from the deck to a seat, shuffling the discard pile back into the
+/// deck whenever it runs out.
+///
+/// # Arguments
+/// * `cards` - Every card of the game, in `card_id` order
+/// * `color` - The seat the cards are dealt to
+/// * `count` - The number of cards to deal
+/// * `rng` - The seat's deal stream from [`turn_rng`]
+///
+/// # Returns
+/// * `Vec<usize>` - The positions in `cards` of the cards dealt, fewer than
+///   `count` if every card is in a player's hand
+pub fn deal_cards(cards: &mut [Card], color: &str, count: u32, rng: &mut StdRng) -> Vec<usize> {
+    let in_deck = |cards: &[Card]| -> Vec<usize> {
+        cards
+            .iter()
+            .enumerate()
+            .filter(|(_, card)| card.owner_color.is_none() && !card.discarded)
+            .map(|(i, _)| i)
+            .collect()
+    };
+
+    let mut dealt = Vec::new();
+    for _ in 0..count {
+        // If deck is empty, shuffle the discard pile back into it. Cards are
+        // dealt at random, so returning them to the deck is all a shuffle takes.
+        let mut deck = in_deck(cards);
+        if deck.is_empty() {
+            for card in cards.iter_mut().filter(|card| card.discarded) {
+                card.discarded = false;
+            }
+            deck = in_deck(cards);
+        }
+
+        // Every card is in a player's hand, so there is nothing left to deal
+        if deck.is_empty() {
+            break;
+        }
+
+        let i = deck[rng.gen_range(0..deck.len())];
+        cards[i].owner_color = Some(color.to_string());
+        dealt.push(i);
+    }
+    dealt
+}
+
+/// Discards cards at random from a seat's hand until it is within the hand size limit.
+///
+/// # Arguments
+/// * `cards` - Every card of the game, in `card_id` order
+/// * `color` - The seat whose hand is checked
+/// * `max_hand_size` - The game's hand size limit, 0 if disabled
+/// * `rng` - The seat's hand limit stream from [`turn_rng`]
+///
+/// # Returns
+/// * `Vec<usize>` - The positions in `cards` of the cards discarded
+pub fn discard_over_hand_limit(
+    cards: &mut [Card],
+    color: &str,
+    max_hand_size: u32,
+    rng: &mut StdRng,
+) -> Vec<usize> {
+    let mut discarded = Vec::new();
+    if max_hand_size == 0 {
+        return discarded;
+    }
+    let mut hand: Vec<usize> = cards
+        .iter()
+        .enumerate()
+        .filter(|(_, card)| card.owner_color.as_deref() == Some(color))
+        .map(|(i, _)| i)
+        .collect();
+    while hand.len() as u32 > max_hand_size {
+        let i = hand.swap_remove(rng.gen_range(0..hand.len()));
+        cards[i].owner_color = None;
+        cards[i].discarded = true;
+        discarded.push(i);
+    }
+    discarded
+}
+
+/// Verifies a hand is within the game's hand size limit, which 0 disables.
+pub fn check_hand_limit(config: &GameConfig, hand_size: u32) -> Result<(), GameError> {
+    if config.max_hand_size > 0 && hand_size > config.max_hand_size {
+        return Err(GameError::HandLimitExceeded {
+            max_hand_size: config.max_hand_size,
+        });
+    }
+    Ok(())
+}
+
+/// Checks whether the cards are a pair, two cards with the same number.
+pub fn is_pair(cards: &[Card]) -> bool {
+    matches!(cards, [a, b] if a.value == b.value)
+}
+
+/// Applies a played hand's tanks and defense buff to a tile, keeping any stronger or longer buff.
+pub fn apply_hand_reward(tile: Tile, reward: HandReward, turn: u64) -> Tile {
+    let (defense_buff, buff_until_turn) = if reward.buff_turns > 0 {
+        (
+            reward.defense_buff.max(active_defense_buff(&tile, turn)),
+            (turn + reward.buff_turns - 1).max(tile.buff_until_turn),
+        )
+    } else {
+        (tile.defense_buff, tile.buff_until_turn)
+    };
+    Tile {
+        tanks: tile.tanks + reward.tanks,
+        defense_buff,
+        buff_until_turn,
+        ..tile
+    }
+}
+
+/// Gives the player the gold from a played hand.
+pub fn apply_hand_gold(player: Player, reward: HandReward) -> Player {
+    Player {
+        gold: player.gold.saturating_add(reward.gold),
+        ..player
+    }
+}
+
+/// Adds the tank built from a pair to a tile.
+pub fn apply_build_tank(tile: Tile) -> Tile {
+    Tile {
+        tanks: tile.tanks + 1,
+        ..tile
+    }
+}
+
+// ------------------------------------------------------------
+// Actions
+// ------------------------------------------------------------
+
+/// Verifies the player has the stamina an action costs.
+pub fn check_stamina(player: &Player, cost: u32) -> Result<(), GameError> {
+    if player.stamina < cost {
+        return Err(GameError::NotEnoughStamina);
+    }
+    Ok(())
+}
+
+/// Verifies a player may build infantry on a tile, see [`build_infantry`](crate::build_infantry).
+///
+/// # Arguments
+/// * `hand_size` - The number of cards in the player's hand
+pub fn check_build_infantry(
+    config: &GameConfig,
+    player: &Player,
+    tile: &Tile,
+    hand_size: u32,
+) -> Result<(), GameError> {
+    if tile.owner_color.as_ref() != Some(&player.color) {
+        return Err(GameError::TileNotOwned);
+    }
+    check_hand_limit(config, hand_size)?;
+    if player.gold < config.infantry_gold_cost {
+        return Err(GameError::NotEnoughGold);
+    }
+    Ok(())
+}
+
+/// Verifies a player may move units between two tiles, see [`move_units`](crate::move_units).
+///
+/// # Arguments
+/// * `hand_size` - The number of cards in the player's hand
+pub fn check_move(
+    config: &GameConfig,
+    player: &Player,
+    from_tile: &Tile,
+    to_tile: &Tile,
+    troops: u32,
+    tanks: u32,
+    hand_size: u32,
+) -> Result<(), GameError> {
+    // Verify units can enter the destination tile
+    if !to_tile.terrain.is_passable() {
+        return Err(GameError::TileImpassable);
+    }
+
+    // Verify ownership of both tiles
+    if from_tile.owner_color.as_ref() != Some(&player.color)
+        || to_tile.owner_color.as_ref() != Some(&player.color)
+    {
+        return Err(GameError::TileNotOwned);
+    }
+
+    // Verify tiles are adjacent
+    if !config
+        .board()
+        .are_adjacent((from_tile.x, from_tile.y), (to_tile.x, to_tile.y), false)
+    {
+        return Err(GameError::TilesNotAdjacent);
+    }
+
+    // Verify enough units to move, keeping at least 1 troop behind
+    if from_tile.troops < troops {
+        return Err(GameError::NotEnoughTroops);
+    }
+    if from_tile.tanks < tanks {
+        return Err(GameError::NotEnoughTanks);
+    }
+    if from_tile.troops - troops < 1 {
+        return Err(GameError::MustKeepTroop);
+    }
+
+    // Verify the player is within the hand size limit and has enough stamina to move
+    check_hand_limit(config, hand_size)?;
+    check_stamina(player, config.move_stamina_cost)
+}
+
+/// Verifies a player may attack one tile from another, see [`attack`](crate::attack).
+///
+/// # Arguments
+/// * `hand_size` - The number of cards in the player's hand
+pub fn check_attack(
+    config: &GameConfig,
+    player: &Player,
+    from_tile: &Tile,
+    to_tile: &Tile,
+    troops: u32,
+    tanks: u32,
+    hand_size: u32,
+) -> Result<(), GameError> {
+    // Verify the attacking player owns the source tile
+    if from_tile.owner_color.as_ref() != Some(&player.color) {
+        return Err(GameError::TileNotOwned);
+    }
+
+    // Verify the player isn't attacking their own tile, or one that can't be entered
+    if to_tile.owner_color.as_ref() == Some(&player.color) {
+        return Err(GameError::CannotAttackOwnTile);
+    }
+    if !to_tile.terrain.is_passable() {
+        return Err(GameError::TileImpassable);
+    }
+
+    // Verify the tiles are adjacent
+    if !config.board().are_adjacent(
+        (from_tile.x, from_tile.y),
+        (to_tile.x, to_tile.y),
+        config.allow_diagonal_attacks,
+    ) {
+        return Err(GameError::TilesNotAdjacent);
+    }
+
+    // Verify at least one unit is committed, and the source tile has enough
+    // units while keeping at least 1 troop behind
+    if troops == 0 && tanks == 0 {
+        return Err(GameError::NoUnitsCommitted);
+    }
+    if from_tile.troops < troops {
+        return Err(GameError::NotEnoughTroops);
+    }
+    if from_tile.tanks < tanks {
+        return Err(GameError::NotEnoughTanks);
+    }
+    if from_tile.troops - troops < 1 {
+        return Err(GameError::MustKeepTroop);
+    }
+
+    // Verify the player is within the hand size limit and has enough stamina to attack
+    check_hand_limit(config, hand_size)?;
+    check_stamina(player, config.attack_stamina_cost)
+}
+
+/// Spends the gold for one infantry and adds it to the tile.
+pub fn apply_build_infantry(config: &GameConfig, player: Player, tile: Tile) -> (Player, Tile) {
+    let player = Player {
+        gold: player.gold - config.infantry_gold_cost,
+        ..player
+    };
+    let tile = Tile {
+        troops: tile.troops + 1,
+        ..tile
+    };
+    (player, tile)
+}
+
+/// Spends the stamina an action costs.
+pub fn spend_stamina(player: Player, cost: u32) -> Player {
+    Player {
+        stamina: player.stamina - cost,
+        ..player
+    }
+}
+
+/// Moves units from one tile to another and spends the stamina the move costs.
+///
+/// # Returns
+/// * `(Player, Tile, Tile)` - The player, source tile and destination tile after the move
+pub fn apply_move(
+    config: &GameConfig,
+    player: Player,
+    from: Tile,
+    to: Tile,
+    troops: u32,
+    tanks: u32,
+) -> (Player, Tile, Tile) {
+    let from = Tile {
+        troops: from.troops - troops,
+        tanks: from.tanks - tanks,
+        ..from
+    };
+    let to = Tile {
+        troops: to.troops + troops,
+        tanks: to.tanks + tanks,
+        ..to
+    };
+    (spend_stamina(player, config.move_stamina_cost), from, to)
+}
+
+// ------------------------------------------------------------
+// Combat
+// ------------------------------------------------------------
+
+/// Returns the defense a tile's buff from played cards adds on the given turn.
+pub fn active_defense_buff(tile: &Tile, turn: u64) -> u32 {
+    if turn <= tile.buff_until_turn {
+        tile.defense_buff
+    } else {
+        0
+    }
+}
+
+/// Applies combat damage to a group of units, with tanks absorbing damage first.
+///
+/// Each tank absorbs damage equal to its power. Damage that cannot destroy a
+/// whole tank, and any damage left once the tanks are gone, is applied to troops
+/// one for one.
+///
+/// # Returns
+/// * `(u32, u32)` - The number of troops and tanks lost
+pub fn apply_damage(troops: u32, tanks: u32, damage: u32, tank_power: u32) -> (u32, u32) {
+    let tanks_lost = (damage / tank_power).min(tanks);
+    let troops_lost = (damage - tanks_lost * tank_power).min(troops);
+    (troops_lost, tanks_lost)
+}
+
+/// The result of an attack, with both tiles as they are after it.
+pub struct AttackOutcome {
+    pub from_tile: Tile,
+    pub to_tile: Tile,
+    pub attack_power: u32,
+    pub defense: u32,
+    pub attacker_troops_lost: u32,
+    pub attacker_tanks_lost: u32,
+    pub defender_troops_lost: u32,
+    pub defender_tanks_lost: u32,
+    pub captured: bool,
+}
+
+/// Resolves an attack that has already been validated.
+///
+/// # Combat Rules
+/// 1. Attack power is 1 per troop plus `tank_power` per tank committed
+/// 2. Defense is the natural defense of neutral tiles or the defending units'
+///    power, plus the terrain's defense bonus and any active buff from played cards
+/// 3. The attacker captures the tile if its power is greater than the defense
+/// 4. Each side takes damage equal to the other side's power (see [`apply_damage`])
+/// 5. On a capture the surviving attackers occupy the tile and its buff is lost,
+///    otherwise they return to the source tile
+pub fn resolve_attack(
+    config: &GameConfig,
+    from_tile: &Tile,
+    to_tile: &Tile,
+    troops: u32,
+    tanks: u32,
+    turn: u64,
+) -> AttackOutcome {
+    // Calculate the units staying behind on the source tile
+    let remaining_troops = from_tile.troops - troops;
+    let remaining_tanks = from_tile.tanks - tanks;
+
+    // Calculate attack power and defense
+    let attack_power = troops + (tanks * config.tank_power);
+    let unit_defense = if to_tile.owner_color.is_none() {
+        config.natural_defense
+    } else {
+        to_tile.troops + (to_tile.tanks * config.tank_power)
+    };
+    let defense =
+        unit_defense + to_tile.terrain.defense_bonus() + active_defense_buff(to_tile, turn);
+    let captured = attack_power > defense;
+
+    // Calculate casualties on both sides
+    let (attacker_troops_lost, attacker_tanks_lost) =
+        apply_damage(troops, tanks, defense, config.tank_power);
+    let (defender_troops_lost, defender_tanks_lost) = apply_damage(
+        to_tile.troops,
+        to_tile.tanks,
+        attack_power,
+        config.tank_power,
+    );
+    let surviving_troops = troops - attacker_troops_lost;
+    let surviving_tanks = tanks - attacker_tanks_lost;
+
+    let (from_tile, to_tile) = if captured {
+        // Remove the committed units from the source tile and capture the
+        // target tile with the surviving attackers
+        (
+            Tile {
+                troops: remaining_troops,
+                tanks: remaining_tanks,
+                ..from_tile.clone()
+            },
+            Tile {
+                owner_color: from_tile.owner_color.clone(),
+                troops: surviving_troops,
+                tanks: surviving_tanks,
+                defense_buff: 0,
+                buff_until_turn: 0,
+                ..to_tile.clone()
+            },
+        )
+    } else {
+        // Return the surviving attackers to the source tile and apply the
+        // defender's losses
+        (
+            Tile {
+                troops: remaining_troops + surviving_troops,
+                tanks: remaining_tanks + surviving_tanks,
+                ..from_tile.clone()
+            },
+            Tile {
+                troops: to_tile.troops - defender_troops_lost,
+                tanks: to_tile.tanks - defender_tanks_lost,
+                ..to_tile.clone()
+            },
+        )
+    };
+    AttackOutcome {
+        from_tile,
+        to_tile,
+        attack_power,
+        defense,
+        attacker_troops_lost,
+        attacker_tanks_lost,
+        defender_troops_lost,
+        defender_tanks_lost,
+        captured,
+    }
+}
+
+// ------------------------------------------------------------
+// Income, Victory and Fog of War
+// ------------------------------------------------------------
+
+/// Works out how much gold a seat earns at the start of its turn from the tiles it owns.
+///
+/// # Income Rules
+/// 1. Every seat earns the game's base gold per turn
+/// 2. Plus 1 gold for every `tiles_per_gold` tiles owned (0 disables)
+/// 3. Plus `gold_mine_income` for every gold mine owned
+/// 4. Plus `region_bonus_gold` for every region of at least `region_bonus_size`
+///    connected tiles (0 disables), where tiles connect to their non-diagonal neighbors
+pub fn calculate_income(config: &GameConfig, tiles: &[Tile], player: &Player) -> PlayerIncome {
+    let owned_tiles: Vec<&Tile> = tiles
+        .iter()
+        .filter(|t| t.owner_color.as_ref() == Some(&player.color))
+        .collect();
+    let tiles_owned = owned_tiles.len() as u32;
+    let gold_mines = owned_tiles
+        .iter()
+        .filter(|t| t.terrain == Terrain::GoldMine)
+        .count() as u32;
+
+    // Measure each connected region of owned tiles
+    let board = config.board();
+    let owned: HashSet<(u32, u32)> = owned_tiles.iter().map(|t| (t.x, t.y)).collect();
+    let mut visited: HashSet<(u32, u32)> = HashSet::new();
+    let mut large_regions = 0;
+    for &start in &owned {
+        if !visited.insert(start) {
+            continue;
+        }
+        let mut region_size = 0;
+        let mut frontier = vec![start];
+        while let Some((x, y)) = frontier.pop() {
+            region_size += 1;
+            for neighbor in board.neighbors(x, y, false) {
+                if owned.contains(&neighbor) && visited.insert(neighbor) {
+                    frontier.push(neighbor);
+                }
+            }
+        }
+        if config.region_bonus_size > 0 && region_size >= config.region_bonus_size {
+            large_regions += 1;
+        }
+    }
+
+    let base = config.gold_per_turn;
+    let territory = tiles_owned.checked_div(config.tiles_per_gold).unwrap_or(0);
+    let mines = gold_mines * config.gold_mine_income;
+    let regions = large_regions * config.region_bonus_gold;
+    PlayerIncome {
+        player_id: player.player_id,
+        game_id: player.game_id,
+        color: player.color.clone(),
+        tiles_owned,
+        base,
+        territory,
+        gold_mines: mines,
+        region_bonus: regions,
+        total: base + territory + mines + regions,
+    }
+}
+
+/// Who a game's victory conditions eliminated, and whether they ended the game.
+pub struct VictoryOutcome {
+    pub eliminated: Vec<String>, // Colors that own no tiles and weren't eliminated before
+    pub finished: bool,
+    pub winner: Option<String>, // None if the game isn't over or ended in a tie
+}
+
+/// Works out a game's victory conditions from its board and turn counter.
+///
+/// Players who no longer own any tiles are eliminated. The game is finished when
+/// one color remains, when a color holds the configured share of the board, or
+/// when the turn limit is passed, in which case the color owning the most tiles
+/// wins (no winner on a tie).
+pub fn evaluate_victory(
+    config: &GameConfig,
+    turn: u64,
+    tiles: &[Tile],
+    players: &[Player],
+) -> VictoryOutcome {
+    // Count the tiles owned by each color, eliminating colors with none left.
+    // Impassable tiles can never be owned, so they don't count towards the board.
+    let total_tiles = tiles.iter().filter(|t| t.terrain.is_passable()).count() as u32;
+    let mut remaining: Vec<(String, u32)> = Vec::new();
+    let mut eliminated = Vec::new();
+    for player in players {
+        let owned = tiles
+            .iter()
+            .filter(|t| t.owner_color.as_ref() == Some(&player.color))
+            .count() as u32;
+        if owned > 0 {
+            remaining.push((player.color.clone(), owned));
+        } else if !player.eliminated {
+            eliminated.push(player.color.clone());
+        }
+    }
+
+    // Find the color owning the most tiles, if no other color is tied with it
+    remaining.sort_by_key(|(_, owned)| std::cmp::Reverse(*owned));
+    let leader = match remaining.as_slice() {
+        [] => None,
+        [only] => Some(only),
+        [first, second, ..] if first.1 > second.1 => Some(first),
+        _ => None,
+    };
+
+    // Decide whether the game is over and who won
+    let holds_territory = leader.is_some_and(|(_, owned)| {
+        config.victory_territory_percent > 0
+            && owned * 100 >= config.victory_territory_percent * total_tiles
+    });
+    let finished = remaining.len() <= 1
+        || holds_territory
+        || (config.turn_limit > 0 && turn > config.turn_limit);
+    VictoryOutcome {
+        eliminated,
+        finished,
+        winner: leader.filter(|_| finished).map(|(color, _)| color.clone()),
+    }
+}
+
+/// Finds the tiles a seat can see the units on in a fog of war game.
+///
+/// A seat sees the tiles it owns and every tile it could attack from them.
+pub fn tiles_in_sight(config: &GameConfig, tiles: &[Tile], color: &str) -> HashSet<(u32, u32)> {
+    let board = config.board();
+    let mut in_sight = HashSet::new();
+    for tile in tiles
+        .iter()
+        .filter(|t| t.owner_color.as_deref() == Some(color))
+    {
+        in_sight.insert((tile.x, tile.y));
+        in_sight.extend(board.neighbors(tile.x, tile.y, config.allow_diagonal_attacks));
+    }
+    in_sight
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::HandRank;
+    use crate::default_game_config;
+    use spacetimedb::Identity;
+
+    fn tile(x: u32, y: u32, owner: Option<&str>, troops: u32, tanks: u32) -> Tile {
+        Tile {
+            tile_id: tile_id(0, x, y),
+            game_id: 0,
+            x,
+            y,
+            terrain: Terrain::Plains,
+            owner_color: owner.map(str::to_string),
+            troops,
+            tanks,
+            defense_buff: 0,
+            buff_until_turn: 0,
+        }
+    }
+
+    fn plains_board(config: &GameConfig) -> Vec<Tile> {
+        config
+            .board()
+            .tiles()
+            .map(|(x, y)| tile(x, y, None, 0, 0))
+            .collect()
+    }
+
+    fn own(tiles: &mut [Tile], color: &str, coords: &[(u32, u32)]) {
+        for t in tiles.iter_mut().filter(|t| coords.contains(&(t.x, t.y))) {
+            t.owner_color = Some(color.to_string());
+            t.troops = 1;
+        }
+    }
+
+    fn player(color: &str) -> Player {
+        Player {
+            player_id: 0,
+            game_id: 0,
+            color: color.to_string(),
+            identity: Some(Identity::ZERO),
+            online: true,
+            gold: 10,
+            stamina: 2,
+            eliminated: false,
+            last_seen: None,
+        }
+    }
+
+    fn game() -> Game {
+        Game {
+            game_id: 0,
+            game_name: "test".to_string(),
+            turn: 1,
+            current_player_index: 0,
+            simultaneous: false,
+            status: GameStatus::InProgress,
+            winner: None,
+            turn_deadline: Timestamp::UNIX_EPOCH,
+            rng_seed: 0,
+        }
+    }
+
+    fn at_secs(secs: i64) -> Timestamp {
+        Timestamp::from_micros_since_unix_epoch(secs * 1_000_000)
+    }
+
+    fn card(suit: &str, value: u8, owner: Option<&str>) -> Card {
+        Card {
+            card_id: 0,
+            game_id: 0,
+            suit: suit.to_string(),
+            value,
+            owner_color: owner.map(str::to_string),
+            discarded: false,
+        }
+    }
+
+    fn hand_of(cards: &[Card], color: &str) -> usize {
+        cards
+            .iter()
+            .filter(|c| c.owner_color.as_deref() == Some(color))
+            .count()
+    }
+
+    // Setup
+
+    #[test]
+    fn default_config_is_valid() {
+        assert_eq!(validate_game_config(&default_game_config()), Ok(()));
+    }
+
+    #[test]
+    fn config_limits_are_enforced() {
+        let invalid = [
+            GameConfig {
+                board_width: MIN_BOARD_SIZE - 1,
+                ..default_game_config()
+            },
+            GameConfig {
+                board_height: MAX_BOARD_SIZE + 1,
+                ..default_game_config()
+            },
+            GameConfig {
+                player_count: 1,
+                ..default_game_config()
+            },
+            GameConfig {
+                player_count: 9,
+                ..default_game_config()
+            },
+            GameConfig {
+                tank_power: 0,
+                ..default_game_config()
+            },
+            GameConfig {
+                turn_duration_secs: 0,
+                ..default_game_config()
+            },
+            GameConfig {
+                tank_power: u32::MAX,
+                ..default_game_config()
+            },
+            GameConfig {
+                turn_duration_secs: u64::MAX,
+                ..default_game_config()
+            },
+            GameConfig {
+                victory_territory_percent: 101,
+                ..default_game_config()
+            },
+            GameConfig {
+                victory_territory_percent: u32::MAX,
+                ..default_game_config()
+            },
+            GameConfig {
+                turn_limit: u64::MAX,
+                ..default_game_config()
+            },
+            GameConfig {
+                offline_grace_secs: u64::MAX,
+                ..default_game_config()
+            },
+            GameConfig {
+                natural_defense: u32::MAX,
+                ..default_game_config()
+            },
+            GameConfig {
+                starting_troops: u32::MAX,
+                ..default_game_config()
+            },
+            GameConfig {
+                gold_per_turn: u32::MAX,
+                ..default_game_config()
+            },
+            GameConfig {
+                gold_mine_income: MAX_GOLD_MINE_INCOME + 1,
+                ..default_game_config()
+            },
+            GameConfig {
+                region_bonus_gold: MAX_REGION_BONUS_GOLD + 1,
+                ..default_game_config()
+            },
+            GameConfig {
+                stamina_per_turn: u32::MAX,
+                ..default_game_config()
+            },
+            GameConfig {
+                max_stamina: u32::MAX,
+                ..default_game_config()
+            },
+        ];
+        for config in &invalid {
+            assert!(matches!(
+                validate_game_config(config),
+                Err(GameError::InvalidGameConfig { .. })
+            ));
+        }
+        let largest = GameConfig {
+            board_width: MAX_BOARD_SIZE,
+            board_height: MAX_BOARD_SIZE,
+            player_count: 8,
+            tank_power: MAX_TANK_POWER,
+            victory_territory_percent: MAX_VICTORY_TERRITORY_PERCENT,
+            turn_limit: MAX_TURN_LIMIT,
+            turn_duration_secs: MAX_TURN_DURATION_SECS,
+            offline_grace_secs: MAX_OFFLINE_GRACE_SECS,
+            natural_defense: MAX_NATURAL_DEFENSE,
+            starting_troops: MAX_STARTING_TROOPS,
+            gold_per_turn: MAX_GOLD_PER_TURN,
+            gold_mine_income: MAX_GOLD_MINE_INCOME,
+            region_bonus_gold: MAX_REGION_BONUS_GOLD,
+            stamina_per_turn: MAX_STAMINA_PER_TURN,
+            max_stamina: MAX_STAMINA_CAP,
+            ..default_game_config()
+        };
+        assert_eq!(validate_game_config(&largest), Ok(()));
+    }
+
+    #[test]
+    fn seats_take_palette_colors_in_order_and_start_empty() {
+        let seats = new_seats(3, &default_game_config());
+        let colors: Vec<&str> = seats.iter().map(|p| p.color.as_str()).collect();
+        assert_eq!(colors, ["red", "green", "yellow", "orange", "purple"]);
+        for seat in &seats {
+            assert_eq!((seat.gold, seat.stamina), (0, 0));
+            assert!(seat.identity.is_none());
+            assert!(!seat.eliminated);
+        }
+    }
+
+    #[test]
+    fn new_board_gives_each_seat_a_base_and_leaves_the_rest_neutral() {
+        let config = default_game_config();
+        let tiles = new_board(3, &config, 42);
+        assert_eq!(tiles.len(), 49);
+        let bases = [(1, 1), (5, 1), (1, 5), (5, 5), (3, 3)];
+        for t in &tiles {
+            assert_eq!(t.tile_id, (3 << 32) | ((t.x as u64) << 16) | t.y as u64);
+            match bases.iter().position(|&base| base == (t.x, t.y)) {
+                Some(seat) => {
+                    assert_eq!(t.owner_color.as_deref(), Some(seat_color(seat as u32)));
+                    assert_eq!(t.troops, config.starting_troops);
+                    assert_eq!(t.terrain, Terrain::Plains);
+                }
+                None => {
+                    assert_eq!(t.owner_color, None);
+                    assert_eq!((t.troops, t.tanks), (0, 0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn new_deck_has_every_card_once() {
+        let deck = new_deck(0);
+        assert_eq!(deck.len(), 52);
+        let faces: HashSet<(&str, u8)> = deck.iter().map(|c| (c.suit.as_str(), c.value)).collect();
+        assert_eq!(faces.len(), 52);
+        assert!(deck.iter().all(|c| c.owner_color.is_none() && !c.discarded));
+        assert!(deck.iter().all(|c| (1..=13).contains(&c.value)));
+    }
+
+    #[test]
+    fn coordinates_off_the_board_never_alias_another_tile() {
+        let config = default_game_config();
+        assert_eq!(board_tile_id(&config, 2, 6, 6), Some(tile_id(2, 6, 6)));
+        // Without the board check these would be tile (0, 1) of game 3 and tile (1, 0) of game 2
+        assert_eq!(tile_id(2, 65536, 1), tile_id(3, 0, 1));
+        assert_eq!(board_tile_id(&config, 2, 65536, 1), None);
+        assert_eq!(board_tile_id(&config, 2, 0, 65536), None);
+        assert_eq!(board_tile_id(&config, 2, 7, 0), None);
+        assert_eq!(board_tile_id(&config, 2, 0, 7), None);
+    }
+
+    // Turns
+
+    #[test]
+    fn turns_start_with_income_and_capped_stamina() {
+        let config = default_game_config();
+        let awarded = award_turn_start(&config, player("red"), 3);
+        assert_eq!((awarded.gold, awarded.stamina), (13, 2));
+        let tired = Player {
+            stamina: 0,
+            ..player("red")
+        };
+        assert_eq!(award_turn_start(&config, tired, 3).stamina, 1);
+        let rich = Player {
+            gold: u32::MAX,
+            ..player("red")
+        };
+        assert_eq!(award_turn_start(&config, rich, 3).gold, u32::MAX);
+    }
+
+    #[test]
+    fn only_the_current_seat_may_act() {
+        let game = game();
+        assert_eq!(check_turn(&game, &player("red"), false), Ok(()));
+        assert_eq!(
+            check_turn(&game, &player("green"), false),
+            Err(GameError::NotYourTurn {
+                current_color: "red".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn simultaneous_games_skip_the_turn_check() {
+        assert_eq!(check_turn(&game(), &player("green"), true), Ok(()));
+    }
+
+    #[test]
+    fn finished_games_and_eliminated_players_cannot_act() {
+        let finished = Game {
+            status: GameStatus::Finished,
+            ..game()
+        };
+        assert_eq!(
+            check_turn(&finished, &player("red"), true),
+            Err(GameError::GameOver)
+        );
+        let eliminated = Player {
+            eliminated: true,
+            ..player("red")
+        };
+        assert_eq!(
+            check_turn(&game(), &eliminated, true),
+            Err(GameError::PlayerEliminated)
+        );
+    }
+
+    #[test]
+    fn offline_players_keep_their_turns_for_the_grace_period() {
+        let offline = Player {
+            online: false,
+            last_seen: Some(at_secs(100)),
+            ..player("red")
+        };
+        assert!(can_take_turn(&offline, at_secs(130), 30));
+        assert!(!can_take_turn(&offline, at_secs(131), 30));
+        assert!(can_take_turn(&player("red"), at_secs(1000), 30));
+    }
+
+    #[test]
+    fn unclaimed_and_eliminated_seats_never_take_turns() {
+        let unclaimed = Player {
+            identity: None,
+            ..player("red")
+        };
+        let eliminated = Player {
+            eliminated: true,
+            ..player("red")
+        };
+        assert!(!can_take_turn(&unclaimed, at_secs(0), 30));
+        assert!(!can_take_turn(&eliminated, at_secs(0), 30));
+    }
+
+    #[test]
+    fn turns_pass_in_seat_order_skipping_seats_that_cannot_play() {
+        let config = default_game_config();
+        let mut players = new_seats(0, &config);
+        for p in players.iter_mut() {
+            p.identity = Some(Identity::ZERO);
+            p.online = true;
+        }
+        players[1].eliminated = true; // green
+        players[2].identity = None; // yellow
+        assert_eq!(next_seat(&config, 0, &players, at_secs(0)), Some(3));
+        assert_eq!(next_seat(&config, 4, &players, at_secs(0)), Some(0));
+
+        for p in players.iter_mut() {
+            p.identity = None;
+        }
+        assert_eq!(next_seat(&config, 0, &players, at_secs(0)), None);
+    }
+
+    #[test]
+    fn a_lone_seat_keeps_the_turn() {
+        let config = default_game_config();
+        let mut players = new_seats(0, &config);
+        players[2].identity = Some(Identity::ZERO);
+        players[2].online = true;
+        assert_eq!(next_seat(&config, 2, &players, at_secs(0)), Some(2));
+    }
+
+    #[test]
+    fn turns_can_only_be_passed_to_another_seat() {
+        let config = default_game_config();
+        let mut players = new_seats(0, &config);
+        players[0].identity = Some(Identity::ZERO);
+        players[0].online = true;
+        assert_eq!(
+            check_pass_turn(&config, 0, &players, at_secs(0)),
+            Err(GameError::NoOtherPlayers)
+        );
+
+        // A seat that has been offline past the grace period doesn't count either
+        players[3].identity = Some(Identity::ZERO);
+        players[3].last_seen = Some(at_secs(0));
+        assert_eq!(
+            check_pass_turn(&config, 0, &players, at_secs(31)),
+            Err(GameError::NoOtherPlayers)
+        );
+        assert_eq!(check_pass_turn(&config, 0, &players, at_secs(30)), Ok(()));
+    }
+
+    #[test]
+    fn the_turn_number_goes_up_when_the_rotation_wraps() {
+        assert_eq!(next_turn_number(4, 0, 3), 4);
+        assert_eq!(next_turn_number(4, 3, 0), 5);
+        assert_eq!(next_turn_number(4, 2, 2), 5);
+    }
+
+    #[test]
+    fn turn_draws_depend_only_on_the_seed_turn_seat_and_stream() {
+        let draws = |seed, turn, seat, stream| -> Vec<u32> {
+            let mut rng = turn_rng(seed, turn, seat, stream);
+            (0..8).map(|_| rng.gen()).collect()
+        };
+        assert_eq!(draws(7, 3, 1, 0), draws(7, 3, 1, 0));
+        assert_ne!(draws(7, 3, 1, 0), draws(8, 3, 1, 0));
+        assert_ne!(draws(7, 3, 1, 0), draws(7, 4, 1, 0));
+        assert_ne!(draws(7, 3, 1, 0), draws(7, 3, 2, 0));
+        assert_ne!(draws(7, 3, 1, 0), draws(7, 3, 1, 1));
+    }
+
+    // Cards
+
+    #[test]
+    fn dealing_moves_cards_from_the_deck_to_the_hand() {
+        let mut cards = new_deck(0);
+        let dealt = deal_cards(&mut cards, "red", 2, &mut turn_rng(1, 1, 0, 0));
+        assert_eq!(dealt.len(), 2);
+        assert_eq!(hand_of(&cards, "red"), 2);
+
+        let mut again = new_deck(0);
+        deal_cards(&mut again, "red", 2, &mut turn_rng(1, 1, 0, 0));
+        let hands = |cards: &[Card]| -> Vec<Option<String>> {
+            cards.iter().map(|c| c.owner_color.clone()).collect()
+        };
+        assert_eq!(hands(&cards), hands(&again));
+    }
+
+    #[test]
+    fn the_discard_pile_is_shuffled_back_once_the_deck_runs_out() {
+        let mut cards = vec![
+            card("hearts", 1, Some("green")),
+            card("hearts", 2, None),
+            Card {
+                discarded: true,
+                ..card("hearts", 3, None)
+            },
+        ];
+        let dealt = deal_cards(&mut cards, "red", 2, &mut turn_rng(1, 1, 0, 0));
+        assert_eq!(dealt.len(), 2);
+        assert_eq!(hand_of(&cards, "red"), 2);
+        assert!(cards.iter().all(|c| !c.discarded));
+    }
+
+    #[test]
+    fn nothing_is_dealt_while_every_card_is_in_a_hand() {
+        let mut cards = vec![card("hearts", 1, Some("green")), card("hearts", 2, None)];
+        let dealt = deal_cards(&mut cards, "red", 2, &mut turn_rng(1, 1, 0, 0));
+        assert_eq!(dealt, vec![1]);
+        assert_eq!(hand_of(&cards, "green"), 1);
+    }
+
+    #[test]
+    fn hands_over_the_limit_are_discarded_down_to_it() {
+        let mut cards: Vec<Card> = (1..=9)
+            .map(|value| card("spades", value, Some("red")))
+            .chain([card("hearts", 1, Some("green"))])
+            .collect();
+        let discarded = discard_over_hand_limit(&mut cards, "red", 7, &mut turn_rng(1, 1, 0, 1));
+        assert_eq!(discarded.len(), 2);
+        assert_eq!(hand_of(&cards, "red"), 7);
+        assert_eq!(hand_of(&cards, "green"), 1);
+        assert_eq!(cards.iter().filter(|c| c.discarded).count(), 2);
+    }
+
+    #[test]
+    fn a_hand_limit_of_zero_keeps_every_card() {
+        let mut cards: Vec<Card> = (1..=9)
+            .map(|value| card("spades", value, Some("red")))
+            .collect();
+        assert!(
+            discard_over_hand_limit(&mut cards, "red", 0, &mut turn_rng(1, 1, 0, 1)).is_empty()
+        );
+        assert_eq!(hand_of(&cards, "red"), 9);
+    }
+
+    #[test]
+    fn players_over_the_hand_limit_cannot_act() {
+        let config = default_game_config();
+        assert_eq!(check_hand_limit(&config, 7), Ok(()));
+        assert_eq!(
+            check_hand_limit(&config, 8),
+            Err(GameError::HandLimitExceeded { max_hand_size: 7 })
+        );
+        let unlimited = GameConfig {
+            max_hand_size: 0,
+            ..default_game_config()
+        };
+        assert_eq!(check_hand_limit(&unlimited, 52), Ok(()));
+    }
+
+    #[test]
+    fn tanks_are_built_from_pairs() {
+        assert!(is_pair(&[card("hearts", 7, None), card("spades", 7, None)]));
+        assert!(!is_pair(&[
+            card("hearts", 7, None),
+            card("hearts", 8, None)
+        ]));
+        assert!(!is_pair(&[card("hearts", 7, None)]));
+    }
+
+    #[test]
+    fn hand_rewards_add_tanks_and_a_buff_from_the_current_turn() {
+        let reward = HandRank::Flush.reward();
+        let rewarded = apply_hand_reward(tile(0, 0, Some("red"), 1, 1), reward, 4);
+        assert_eq!(rewarded.tanks, 3);
+        assert_eq!((rewarded.defense_buff, rewarded.buff_until_turn), (2, 5));
+        assert_eq!(active_defense_buff(&rewarded, 4), 2);
+        assert_eq!(active_defense_buff(&rewarded, 5), 2);
+        assert_eq!(active_defense_buff(&rewarded, 6), 0);
+    }
+
+    #[test]
+    fn tiles_keep_the_stronger_and_longer_buff() {
+        let buffed = Tile {
+            defense_buff: 3,
+            buff_until_turn: 4,
+            ..tile(0, 0, Some("red"), 1, 0)
+        };
+        let rewarded = apply_hand_reward(buffed.clone(), HandRank::Flush.reward(), 4);
+        assert_eq!((rewarded.defense_buff, rewarded.buff_until_turn), (3, 5));
+
+        let unbuffed = apply_hand_reward(buffed, HandRank::Pair.reward(), 4);
+        assert_eq!((unbuffed.defense_buff, unbuffed.buff_until_turn), (3, 4));
+        assert_eq!(unbuffed.tanks, 1);
+    }
+
+    // Actions
+
+    #[test]
+    fn builds_and_moves_change_units_and_spend_their_cost() {
+        let config = default_game_config();
+        let (built_by, built_on) =
+            apply_build_infantry(&config, player("red"), tile(1, 1, Some("red"), 1, 0));
+        assert_eq!((built_by.gold, built_on.troops), (9, 2));
+        assert_eq!(apply_build_tank(tile(1, 1, Some("red"), 1, 0)).tanks, 1);
+        assert_eq!(
+            apply_hand_gold(player("red"), HandRank::Straight.reward()).gold,
+            13
+        );
+
+        let (moved_by, from, to) = apply_move(
+            &config,
+            player("red"),
+            tile(1, 1, Some("red"), 3, 1),
+            tile(1, 2, Some("red"), 1, 0),
+            2,
+            1,
+        );
+        assert_eq!(moved_by.stamina, 1);
+        assert_eq!((from.troops, from.tanks), (1, 0));
+        assert_eq!((to.troops, to.tanks), (3, 1));
+    }
+
+    #[test]
+    fn infantry_needs_an_owned_tile_gold_and_a_legal_hand() {
+        let config = default_game_config();
+        let owned = tile(1, 1, Some("red"), 1, 0);
+        assert_eq!(
+            check_build_infantry(&config, &player("red"), &owned, 0),
+            Ok(())
+        );
+        assert_eq!(
+            check_build_infantry(&config, &player("red"), &tile(1, 1, None, 0, 0), 0),
+            Err(GameError::TileNotOwned)
+        );
+        assert_eq!(
+            check_build_infantry(&config, &player("red"), &owned, 8),
+            Err(GameError::HandLimitExceeded { max_hand_size: 7 })
+        );
+        let broke = Player {
+            gold: 0,
+            ..player("red")
+        };
+        assert_eq!(
+            check_build_infantry(&config, &broke, &owned, 0),
+            Err(GameError::NotEnoughGold)
+        );
+    }
+
+    #[test]
+    fn moves_follow_the_move_rules() {
+        let config = default_game_config();
+        let red = player("red");
+        let from = tile(1, 1, Some("red"), 3, 1);
+        let to = tile(1, 2, Some("red"), 0, 0);
+        assert_eq!(check_move(&config, &red, &from, &to, 2, 1, 0), Ok(()));
+
+        let water = Tile {
+            terrain: Terrain::Water,
+            ..to.clone()
+        };
+        let cases = [
+            (&from, &water, 1, 0, GameError::TileImpassable),
+            (
+                &from,
+                &tile(1, 2, None, 0, 0),
+                1,
+                0,
+                GameError::TileNotOwned,
+            ),
+            (
+                &from,
+                &tile(2, 2, Some("red"), 0, 0),
+                1,
+                0,
+                GameError::TilesNotAdjacent,
+            ),
+            (&from, &to, 4, 0, GameError::NotEnoughTroops),
+            (&from, &to, 1, 2, GameError::NotEnoughTanks),
+            (&from, &to, 3, 0, GameError::MustKeepTroop),
+        ];
+        for (from, to, troops, tanks, error) in cases {
+            assert_eq!(
+                check_move(&config, &red, from, to, troops, tanks, 0),
+                Err(error)
+            );
+        }
+
+        assert_eq!(
+            check_move(&config, &red, &from, &to, 1, 0, 8),
+            Err(GameError::HandLimitExceeded { max_hand_size: 7 })
+        );
+        let tired = Player {
+            stamina: 0,
+            ..player("red")
+        };
+        assert_eq!(
+            check_move(&config, &tired, &from, &to, 1, 0, 0),
+            Err(GameError::NotEnoughStamina)
+        );
+    }
+
+    #[test]
+    fn moves_are_never_diagonal() {
+        let config = GameConfig {
+            allow_diagonal_attacks: true,
+            ..default_game_config()
+        };
+        let from = tile(1, 1, Some("red"), 3, 0);
+        let to = tile(2, 2, Some("red"), 0, 0);
+        assert_eq!(
+            check_move(&config, &player("red"), &from, &to, 1, 0, 0),
+            Err(GameError::TilesNotAdjacent)
+        );
+    }
+
+    #[test]
+    fn attacks_follow_the_attack_rules() {
+        let config = default_game_config();
+        let red = player("red");
+        let from = tile(1, 1, Some("red"), 3, 1);
+        let to = tile(1, 2, Some("green"), 1, 0);
+        assert_eq!(check_attack(&config, &red, &from, &to, 2, 1, 0), Ok(()));
+
+        let water = Tile {
+            terrain: Terrain::Water,
+            ..tile(1, 2, None, 0, 0)
+        };
+        let cases = [
+            (
+                &tile(1, 1, Some("green"), 3, 0),
+                &to,
+                1,
+                0,
+                GameError::TileNotOwned,
+            ),
+            (
+                &from,
+                &tile(1, 2, Some("red"), 1, 0),
+                1,
+                0,
+                GameError::CannotAttackOwnTile,
+            ),
+            (&from, &water, 1, 0, GameError::TileImpassable),
+            (
+                &from,
+                &tile(2, 2, Some("green"), 1, 0),
+                1,
+                0,
+                GameError::TilesNotAdjacent,
+            ),
+            (&from, &to, 0, 0, GameError::NoUnitsCommitted),
+            (&from, &to, 4, 0, GameError::NotEnoughTroops),
+            (&from, &to, 1, 2, GameError::NotEnoughTanks),
+            (&from, &to, 3, 0, GameError::MustKeepTroop),
+        ];
+        for (from, to, troops, tanks, error) in cases {
+            assert_eq!(
+                check_attack(&config, &red, from, to, troops, tanks, 0),
+                Err(error)
+            );
+        }
+
+        assert_eq!(
+            check_attack(&config, &red, &from, &to, 1, 0, 8),
+            Err(GameError::HandLimitExceeded { max_hand_size: 7 })
+        );
+        let tired = Player {
+            stamina: 0,
+            ..player("red")
+        };
+        assert_eq!(
+            check_attack(&config, &tired, &from, &to, 1, 0, 0),
+            Err(GameError::NotEnoughStamina)
+        );
+    }
+
+    #[test]
+    fn diagonal_attacks_need_the_config_option() {
+        let from = tile(1, 1, Some("red"), 3, 0);
+        let to = tile(2, 2, None, 0, 0);
+        assert_eq!(
+            check_attack(&default_game_config(), &player("red"), &from, &to, 1, 0, 0),
+            Err(GameError::TilesNotAdjacent)
+        );
+        let diagonal = GameConfig {
+            allow_diagonal_attacks: true,
+            ..default_game_config()
+        };
+        assert_eq!(
+            check_attack(&diagonal, &player("red"), &from, &to, 1, 0, 0),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn hex_boards_attack_along_their_six_neighbors() {
+        let config = GameConfig {
+            topology: crate::board::Topology::Hex,
+            ..default_game_config()
+        };
+        let from = tile(2, 2, Some("red"), 3, 0);
+        let red = player("red");
+        assert_eq!(
+            check_attack(&config, &red, &from, &tile(3, 1, None, 0, 0), 1, 0, 0),
+            Ok(())
+        );
+        assert_eq!(
+            check_attack(&config, &red, &from, &tile(3, 3, None, 0, 0), 1, 0, 0),
+            Err(GameError::TilesNotAdjacent)
+        );
+    }
+
+    // Combat
+
+    #[test]
+    fn tanks_absorb_damage_before_troops() {
+        assert_eq!(apply_damage(3, 2, 5, 2), (1, 2));
+        assert_eq!(apply_damage(3, 2, 3, 2), (1, 1));
+        assert_eq!(apply_damage(3, 0, 10, 2), (3, 0));
+        assert_eq!(apply_damage(0, 2, 1, 2), (0, 0));
+    }
+
+    #[test]
+    fn neutral_tiles_defend_with_natural_defense_and_terrain() {
+        let config = default_game_config();
+        let from = tile(1, 1, Some("red"), 5, 0);
+        let hills = Tile {
+            terrain: Terrain::Hills,
+            ..tile(1, 2, None, 0, 0)
+        };
+        let outcome = resolve_attack(&config, &from, &hills, 3, 0, 1);
+        assert_eq!((outcome.attack_power, outcome.defense), (3, 3));
+        assert!(!outcome.captured);
+
+        let outcome = resolve_attack(&config, &from, &hills, 4, 0, 1);
+        assert_eq!(outcome.defense, 3);
+        assert!(outcome.captured);
+    }
+
+    #[test]
+    fn captures_move_the_survivors_and_drop_the_buff() {
+        let config = default_game_config();
+        let from = tile(1, 1, Some("red"), 6, 2);
+        let to = Tile {
+            defense_buff: 1,
+            buff_until_turn: 3,
+            ..tile(1, 2, Some("green"), 2, 0)
+        };
+        // 3 troops + 2 tanks give 7 power against 2 troops + 1 buff
+        let outcome = resolve_attack(&config, &from, &to, 3, 2, 2);
+        assert_eq!((outcome.attack_power, outcome.defense), (7, 3));
+        assert!(outcome.captured);
+        assert_eq!(
+            (outcome.attacker_troops_lost, outcome.attacker_tanks_lost),
+            (1, 1)
+        );
+        assert_eq!(
+            (outcome.defender_troops_lost, outcome.defender_tanks_lost),
+            (2, 0)
+        );
+        assert_eq!((outcome.from_tile.troops, outcome.from_tile.tanks), (3, 0));
+        assert_eq!(outcome.to_tile.owner_color.as_deref(), Some("red"));
+        assert_eq!((outcome.to_tile.troops, outcome.to_tile.tanks), (2, 1));
+        assert_eq!(
+            (
+                outcome.to_tile.defense_buff,
+                outcome.to_tile.buff_until_turn
+            ),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn failed_attacks_return_the_survivors() {
+        let config = default_game_config();
+        let from = tile(1, 1, Some("red"), 5, 0);
+        let to = tile(1, 2, Some("green"), 3, 1);
+        let outcome = resolve_attack(&config, &from, &to, 3, 0, 1);
+        assert_eq!((outcome.attack_power, outcome.defense), (3, 5));
+        assert!(!outcome.captured);
+        assert_eq!(outcome.attacker_troops_lost, 3);
+        assert_eq!(
+            (outcome.defender_troops_lost, outcome.defender_tanks_lost),
+            (1, 1)
+        );
+        assert_eq!(outcome.from_tile.troops, 2);
+        assert_eq!(outcome.to_tile.owner_color.as_deref(), Some("green"));
+        assert_eq!((outcome.to_tile.troops, outcome.to_tile.tanks), (2, 0));
+    }
+
+    #[test]
+    fn expired_buffs_no_longer_defend() {
+        let config = default_game_config();
+        let from = tile(1, 1, Some("red"), 5, 0);
+        let to = Tile {
+            defense_buff: 2,
+            buff_until_turn: 3,
+            ..tile(1, 2, Some("green"), 1, 0)
+        };
+        assert_eq!(resolve_attack(&config, &from, &to, 1, 0, 3).defense, 3);
+        assert_eq!(resolve_attack(&config, &from, &to, 1, 0, 4).defense, 1);
+    }
+
+    // Income, victory and fog of war
+
+    #[test]
+    fn income_grows_with_territory_gold_mines_and_regions() {
+        let config = default_game_config();
+        let mut tiles = plains_board(&config);
+        let red = player("red");
+        own(&mut tiles, "red", &[(0, 0)]);
+        let income = calculate_income(&config, &tiles, &red);
+        assert_eq!((income.base, income.territory, income.total), (2, 0, 2));
+
+        // A row of 5 connected tiles, one of them a gold mine
+        own(&mut tiles, "red", &[(1, 0), (2, 0), (3, 0), (4, 0)]);
+        tiles[0].terrain = Terrain::GoldMine;
+        let income = calculate_income(&config, &tiles, &red);
+        assert_eq!(income.tiles_owned, 5);
+        assert_eq!(income.territory, 1);
+        assert_eq!(income.gold_mines, 1);
+        assert_eq!(income.region_bonus, 2);
+        assert_eq!(income.total, 6);
+    }
+
+    #[test]
+    fn regions_do_not_connect_diagonally() {
+        let config = default_game_config();
+        let mut tiles = plains_board(&config);
+        own(&mut tiles, "red", &[(0, 0), (1, 1), (2, 2), (3, 3), (4, 4)]);
+        let income = calculate_income(&config, &tiles, &player("red"));
+        assert_eq!(income.region_bonus, 0);
+    }
+
+    #[test]
+    fn players_without_tiles_are_eliminated() {
+        let config = default_game_config();
+        let mut tiles = plains_board(&config);
+        own(&mut tiles, "red", &[(1, 1)]);
+        own(&mut tiles, "green", &[(5, 5)]);
+        let players = [player("red"), player("green"), player("yellow")];
+        let outcome = evaluate_victory(&config, 1, &tiles, &players);
+        assert_eq!(outcome.eliminated, ["yellow"]);
+        assert!(!outcome.finished);
+
+        let already = [
+            player("red"),
+            player("green"),
+            Player {
+                eliminated: true,
+                ..player("yellow")
+            },
+        ];
+        assert!(evaluate_victory(&config, 1, &tiles, &already)
+            .eliminated
+            .is_empty());
+    }
+
+    #[test]
+    fn the_last_color_standing_wins() {
+        let config = default_game_config();
+        let mut tiles = plains_board(&config);
+        own(&mut tiles, "red", &[(1, 1)]);
+        let players = [player("red"), player("green")];
+        let outcome = evaluate_victory(&config, 1, &tiles, &players);
+        assert!(outcome.finished);
+        assert_eq!(outcome.winner.as_deref(), Some("red"));
+    }
+
+    #[test]
+    fn holding_the_territory_share_of_passable_tiles_wins() {
+        let config = GameConfig {
+            board_width: 5,
+            board_height: 5,
+            ..default_game_config()
+        };
+        let mut tiles = plains_board(&config);
+        let coords: Vec<(u32, u32)> = config.board().tiles().take(14).collect();
+        own(&mut tiles, "red", &coords);
+        own(&mut tiles, "green", &[(4, 4)]);
+        let players = [player("red"), player("green")];
+        assert!(!evaluate_victory(&config, 1, &tiles, &players).finished);
+
+        // With 2 water tiles, 14 of the 23 passable tiles is over 60%
+        for t in tiles
+            .iter_mut()
+            .filter(|t| [(4, 2), (4, 3)].contains(&(t.x, t.y)))
+        {
+            t.terrain = Terrain::Water;
+        }
+        let outcome = evaluate_victory(&config, 1, &tiles, &players);
+        assert!(outcome.finished);
+        assert_eq!(outcome.winner.as_deref(), Some("red"));
+
+        let disabled = GameConfig {
+            victory_territory_percent: 0,
+            ..config
+        };
+        assert!(!evaluate_victory(&disabled, 1, &tiles, &players).finished);
+    }
+
+    #[test]
+    fn the_turn_limit_ends_the_game_with_the_largest_territory_winning() {
+        let config = default_game_config();
+        let mut tiles = plains_board(&config);
+        own(&mut tiles, "red", &[(1, 1), (1, 2)]);
+        own(&mut tiles, "green", &[(5, 5)]);
+        let players = [player("red"), player("green")];
+        assert!(!evaluate_victory(&config, 100, &tiles, &players).finished);
+        let outcome = evaluate_victory(&config, 101, &tiles, &players);
+        assert!(outcome.finished);
+        assert_eq!(outcome.winner.as_deref(), Some("red"));
+
+        own(&mut tiles, "green", &[(5, 4)]);
+        let outcome = evaluate_victory(&config, 101, &tiles, &players);
+        assert!(outcome.finished);
+        assert_eq!(outcome.winner, None);
+
+        let disabled = GameConfig {
+            turn_limit: 0,
+            ..default_game_config()
+        };
+        assert!(!evaluate_victory(&disabled, 1000, &tiles, &players).finished);
+    }
+
+    #[test]
+    fn players_see_their_tiles_and_the_tiles_next_to_them() {
+        let config = default_game_config();
+        let mut tiles = plains_board(&config);
+        own(&mut tiles, "red", &[(0, 0)]);
+        let in_sight = tiles_in_sight(&config, &tiles, "red");
+        assert_eq!(in_sight, HashSet::from([(0, 0), (1, 0), (0, 1)]));
+
+        let diagonal = GameConfig {
+            allow_diagonal_attacks: true,
+            ..default_game_config()
+        };
+        assert!(tiles_in_sight(&diagonal, &tiles, "red").contains(&(1, 1)));
+    }
+}
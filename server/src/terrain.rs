@@ -0,0 +1,131 @@
+use crate::board::Board;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// ------------------------------------------------------------
+// Terrain
+// ------------------------------------------------------------
+
+/// The ground a tile is made of, which shapes how it can be fought over.
+#[derive(spacetimedb::SpacetimeType, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Terrain {
+    Plains,
+    Hills,
+    Forest,
+    Water,
+    GoldMine,
+}
+
+// Chance out of 100 of a tile getting each terrain, the rest are plains
+const HILLS_CHANCE: u32 = 15;
+const FOREST_CHANCE: u32 = 15;
+const WATER_CHANCE: u32 = 12;
+const GOLD_MINE_CHANCE: u32 = 8;
+
+impl Terrain {
+    /// Returns the defense added to a tile of this terrain when it is attacked.
+    pub fn defense_bonus(self) -> u32 {
+        match self {
+            Terrain::Hills => 2,
+            Terrain::Forest => 1,
+            Terrain::Plains | Terrain::Water | Terrain::GoldMine => 0,
+        }
+    }
+
+    /// Returns whether units can move onto or attack a tile of this terrain.
+    pub fn is_passable(self) -> bool {
+        self != Terrain::Water
+    }
+}
+
+/// Generates the terrain of a board from a seed.
+///
+/// The same seed and board always give the same map, so a game's map can be
+/// rebuilt from the seed stored on it. Start tiles are always plains, and their
+/// neighbors are never water so no base is cut off.
+///
+/// # Returns
+/// * `Vec<Vec<Terrain>>` - The terrain of each tile, indexed by `[x][y]`
+pub fn generate_terrain(seed: u64, board: &Board, starts: &[(u32, u32)]) -> Vec<Vec<Terrain>> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut map = Vec::with_capacity(board.width as usize);
+    for x in 0..board.width {
+        let mut column = Vec::with_capacity(board.height as usize);
+        for y in 0..board.height {
+            // Roll every tile, even start tiles, so each tile's roll only depends on its position
+            let roll = rng.gen_range(0..100);
+            let mut terrain = if roll < HILLS_CHANCE {
+                Terrain::Hills
+            } else if roll < HILLS_CHANCE + FOREST_CHANCE {
+                Terrain::Forest
+            } else if roll < HILLS_CHANCE + FOREST_CHANCE + WATER_CHANCE {
+                Terrain::Water
+            } else if roll < HILLS_CHANCE + FOREST_CHANCE + WATER_CHANCE + GOLD_MINE_CHANCE {
+                Terrain::GoldMine
+            } else {
+                Terrain::Plains
+            };
+
+            // Keep bases and their surroundings playable
+            let is_start = starts.contains(&(x, y));
+            let next_to_start = starts
+                .iter()
+                .any(|&start| board.are_adjacent(start, (x, y), false));
+            if is_start || (next_to_start && terrain == Terrain::Water) {
+                terrain = Terrain::Plains;
+            }
+            column.push(terrain);
+        }
+        map.push(column);
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Topology;
+
+    const BOARD: Board = Board {
+        topology: Topology::Rectangular,
+        width: 7,
+        height: 7,
+    };
+
+    #[test]
+    fn the_same_seed_gives_the_same_map() {
+        let starts = BOARD.start_positions(5);
+        assert_eq!(
+            generate_terrain(7, &BOARD, &starts),
+            generate_terrain(7, &BOARD, &starts)
+        );
+        assert_ne!(
+            generate_terrain(7, &BOARD, &starts),
+            generate_terrain(8, &BOARD, &starts)
+        );
+    }
+
+    #[test]
+    fn bases_are_plains_and_never_next_to_water() {
+        let starts = BOARD.start_positions(5);
+        for seed in 0..50 {
+            let map = generate_terrain(seed, &BOARD, &starts);
+            for &(x, y) in &starts {
+                assert_eq!(map[x as usize][y as usize], Terrain::Plains);
+                for (nx, ny) in BOARD.neighbors(x, y, false) {
+                    assert_ne!(map[nx as usize][ny as usize], Terrain::Water);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn terrain_shapes_defense_and_movement() {
+        assert_eq!(Terrain::Hills.defense_bonus(), 2);
+        assert_eq!(Terrain::Forest.defense_bonus(), 1);
+        assert_eq!(Terrain::Plains.defense_bonus(), 0);
+        assert_eq!(Terrain::GoldMine.defense_bonus(), 0);
+        assert!(!Terrain::Water.is_passable());
+        assert!(Terrain::Hills.is_passable() && Terrain::GoldMine.is_passable());
+    }
+}